@@ -0,0 +1,447 @@
+use gumdrop::Options;
+use std::collections::HashMap;
+use std::io::{BufRead as _, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// length of each segment, in seconds
+const SEGMENT: u64 = 2;
+// how many segments are in a media playlist
+const WINDOW: u64 = 6;
+// how many segments the streams that have ended have
+const ENDED: u64 = 3;
+// for the `ads` channel, the last few segments of every cycle are an ad break
+const AD_CYCLE: u64 = 15;
+const AD_LENGTH: u64 = 5;
+
+// the channels `/helix/streams/followed` says are live
+const FOLLOWED: &[&str] = &["ads", "multiaudio", "unstable"];
+
+struct Variant {
+    name: &'static str,
+    resolution: Option<&'static str>,
+    bandwidth: u32,
+}
+
+const VARIANTS: &[Variant] = &[
+    Variant {
+        name: "chunked",
+        resolution: Some("1920x1080"),
+        bandwidth: 6_000_000,
+    },
+    Variant {
+        name: "720p60",
+        resolution: Some("1280x720"),
+        bandwidth: 3_000_000,
+    },
+    Variant {
+        name: "480p30",
+        resolution: Some("852x480"),
+        bandwidth: 1_400_000,
+    },
+    Variant {
+        name: "160p30",
+        resolution: Some("284x160"),
+        bandwidth: 300_000,
+    },
+];
+
+const AUDIO_ONLY: Variant = Variant {
+    name: "audio_only",
+    resolution: None,
+    bandwidth: 160_000,
+};
+
+struct Audio {
+    name: &'static str,
+    language: &'static str,
+    default: bool,
+}
+
+// alternate audio tracks for the `multiaudio` channel
+const AUDIO: &[Audio] = &[
+    Audio {
+        name: "English",
+        language: "en",
+        default: true,
+    },
+    Audio {
+        name: "Commentary",
+        language: "en",
+        default: false,
+    },
+    Audio {
+        name: "Deutsch",
+        language: "de",
+        default: false,
+    },
+];
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::new(404, "text/plain", "not found")
+    }
+
+    fn write_to(&self, mut w: impl std::io::Write, keep_alive: bool) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        };
+        write!(
+            w,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len(),
+            if keep_alive { "keep-alive" } else { "close" }
+        )?;
+        w.write_all(&self.body)?;
+        w.flush()
+    }
+}
+
+struct Server {
+    base: String,
+    // how many times each channel's media playlist was requested
+    requests: Mutex<HashMap<String, usize>>,
+}
+
+impl Server {
+    // serves requests on the connection until the client closes it
+    fn handle(&self, stream: TcpStream) -> std::io::Result<()> {
+        // the headers and the body are written separately
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+            // only `Connection` and `Authorization` matter here
+            let mut keep_alive = line.trim_end().ends_with("HTTP/1.1");
+            let mut authorized = false;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("connection") {
+                        keep_alive = !value.trim().eq_ignore_ascii_case("close");
+                    }
+                    if name.trim().eq_ignore_ascii_case("authorization") {
+                        authorized = value.trim().starts_with("Bearer ");
+                    }
+                }
+            }
+
+            let (route, query) = path.split_once('?').unwrap_or((&path, ""));
+            let resp = match route {
+                "/helix/users" | "/helix/streams/followed" if !authorized => Response::new(
+                    401,
+                    "application/json",
+                    r#"{"error":"Unauthorized","status":401,"message":"OAuth token is missing"}"#,
+                ),
+                "/helix/users" => Response::new(
+                    200,
+                    "application/json",
+                    r#"{"data":[{"id":"1","login":"mock"}]}"#,
+                ),
+                "/helix/streams/followed" => followed(query),
+                route => self.route(route),
+            };
+            eprintln!("{} {}", resp.status, path);
+            resp.write_to(&stream, keep_alive)?;
+            if !keep_alive {
+                return Ok(());
+            }
+        }
+    }
+
+    fn route(&self, path: &str) -> Response {
+        let parts = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["api", "channels", channel, "access_token"] => access_token(channel),
+            ["api", "channel", "hls", file] if file.ends_with(".m3u8") => {
+                self.master(file.trim_end_matches(".m3u8"))
+            }
+            ["hls", channel, file] if file.ends_with(".m3u8") => {
+                self.media(channel, file.trim_end_matches(".m3u8"))
+            }
+            ["hls", "flaky", _, "1.ts"] => Response::new(500, "text/plain", "segment is missing"),
+            ["hls", _, _, file] if file.ends_with(".ts") => segment(),
+            _ => Response::not_found(),
+        }
+    }
+
+    fn master(&self, channel: &str) -> Response {
+        let variants = match channel {
+            "offline" => {
+                return Response::new(
+                    404,
+                    "application/json",
+                    r#"[{"url":"","error":"Can not find channel","type":"error"}]"#,
+                )
+            }
+            "forbidden" => {
+                return Response::new(
+                    403,
+                    "application/json",
+                    r#"[{"url":"","error":"token is invalid","type":"error"}]"#,
+                )
+            }
+            // an audio_only variant has no resolution
+            "audioonly" => VARIANTS
+                .iter()
+                .chain(std::iter::once(&AUDIO_ONLY))
+                .collect(),
+            // every other minute the lowest variant goes away and the node changes
+            "unstable" if (now() / 60) % 2 == 1 => VARIANTS[..VARIANTS.len() - 1].iter().collect(),
+            _ => VARIANTS.iter().collect::<Vec<_>>(),
+        };
+
+        let node = match channel {
+            "unstable" => (now() / 60) % 2,
+            _ => 0,
+        };
+
+        let mut out = String::from("#EXTM3U\n");
+        out.push_str(&format!(
+            "#EXT-X-TWITCH-INFO:NODE=\"video-edge-mock{}.local\",MANIFEST-NODE=\"video-weaver.local\",SERVER-TIME=\"{}.00\",CLUSTER=\"local\"\n",
+            node,
+            now()
+        ));
+        let multiaudio = channel == "multiaudio";
+        if multiaudio {
+            for audio in AUDIO {
+                out.push_str(&format!(
+                    "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"{}\",LANGUAGE=\"{}\",AUTOSELECT=YES,DEFAULT={},URI=\"{}/hls/{}/audio-{}.m3u8\"\n",
+                    audio.name,
+                    audio.language,
+                    if audio.default { "YES" } else { "NO" },
+                    self.base,
+                    channel,
+                    audio.name.to_ascii_lowercase()
+                ));
+            }
+        }
+
+        for variant in variants {
+            out.push_str(&format!(
+                "#EXT-X-MEDIA:TYPE=VIDEO,GROUP-ID=\"{0}\",NAME=\"{0}\",AUTOSELECT=YES,DEFAULT=YES\n",
+                variant.name
+            ));
+            out.push_str("#EXT-X-STREAM-INF:PROGRAM-ID=1,");
+            // BANDWIDTH is required by the spec, but not every server sends it
+            if channel != "nobandwidth" {
+                out.push_str(&format!("BANDWIDTH={},", variant.bandwidth));
+            }
+            if let Some(resolution) = variant.resolution {
+                out.push_str(&format!("RESOLUTION={},", resolution));
+            }
+            out.push_str(&format!(
+                "CODECS=\"avc1.4D401F,mp4a.40.2\",VIDEO=\"{}\"",
+                variant.name
+            ));
+            if multiaudio {
+                out.push_str(",AUDIO=\"aac\"");
+            }
+            out.push('\n');
+            out.push_str(&format!(
+                "{}/hls/{}/{}.m3u8\n",
+                self.base, channel, variant.name
+            ));
+        }
+        Response::new(200, "application/vnd.apple.mpegurl", out)
+    }
+
+    fn media(&self, channel: &str, variant: &str) -> Response {
+        let requests = {
+            let mut requests = match self.requests.lock() {
+                Ok(requests) => requests,
+                Err(err) => err.into_inner(),
+            };
+            let count = requests.entry(channel.to_string()).or_default();
+            *count += 1;
+            *count
+        };
+
+        let (sequence, ended) = match channel {
+            "ended" => (0, true),
+            "flaky" if requests == 1 => {
+                return Response::new(503, "text/plain", "try again later");
+            }
+            "flaky" => (0, true),
+            // the stream restarts after the first request, from sequence 0
+            "restart" if requests == 1 => (100, false),
+            "restart" => (0, true),
+            _ => (now() / SEGMENT, false),
+        };
+        let window = if ended { ENDED } else { WINDOW };
+
+        let mut out = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+        out.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", SEGMENT));
+        out.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", sequence));
+        for n in sequence..sequence + window {
+            let ads = channel == "ads";
+            if ads && n % AD_CYCLE == AD_CYCLE - AD_LENGTH {
+                out.push_str("#EXT-X-DISCONTINUITY\n");
+                out.push_str(&format!(
+                    "#EXT-X-DATERANGE:ID=\"stitched-ad-{}\",CLASS=\"twitch-stitched-ad\",START-DATE=\"{}\",DURATION={}.000\n",
+                    n / AD_CYCLE,
+                    n * SEGMENT,
+                    AD_LENGTH * SEGMENT
+                ));
+            }
+            if ads && n % AD_CYCLE == 0 {
+                out.push_str("#EXT-X-DISCONTINUITY\n");
+            }
+
+            let title = if ads && n % AD_CYCLE >= AD_CYCLE - AD_LENGTH {
+                "Amazon"
+            } else {
+                "live"
+            };
+            out.push_str(&format!("#EXTINF:{}.000,{}\n", SEGMENT, title));
+            out.push_str(&format!(
+                "{}/hls/{}/{}/{}.ts\n",
+                self.base, channel, variant, n
+            ));
+        }
+        if ended {
+            out.push_str("#EXT-X-ENDLIST\n");
+        }
+        Response::new(200, "application/vnd.apple.mpegurl", out)
+    }
+}
+
+fn access_token(channel: &str) -> Response {
+    if channel == "notoken" {
+        return Response::new(200, "application/json", r#"{"sig":"mock"}"#);
+    }
+
+    let token = serde_json::json!({
+        "channel": channel,
+        "expires": now() + 60 * 20,
+        "user_ip": "127.0.0.1",
+    });
+    let body = serde_json::json!({
+        "token": token.to_string(),
+        "sig": "0123456789abcdef0123456789abcdef01234567",
+        "mobile_restricted": false,
+    });
+    Response::new(200, "application/json", body.to_string())
+}
+
+// the live channels followed by the mock user, two per page
+fn followed(query: &str) -> Response {
+    let after = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("after="))
+        .and_then(|after| after.parse::<usize>().ok())
+        .unwrap_or_default();
+
+    let data = FOLLOWED
+        .iter()
+        .skip(after)
+        .take(2)
+        .map(|channel| serde_json::json!({ "user_login": channel, "type": "live" }))
+        .collect::<Vec<_>>();
+    let pagination = match after + 2 {
+        next if next < FOLLOWED.len() => serde_json::json!({ "cursor": next.to_string() }),
+        _ => serde_json::json!({}),
+    };
+
+    let body = serde_json::json!({ "data": data, "pagination": pagination });
+    Response::new(200, "application/json", body.to_string())
+}
+
+// null mpeg-ts packets, enough for a player to accept it as a transport stream
+fn segment() -> Response {
+    let mut packet = [0xFF_u8; 188];
+    packet[..4].copy_from_slice(&[0x47, 0x1F, 0xFF, 0x10]);
+    Response::new(200, "video/mp2t", packet.repeat(64))
+}
+
+// how far `--epoch` moved the clock, in seconds
+static OFFSET: AtomicI64 = AtomicI64::new(0);
+
+fn clock() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    (clock() as i64 + OFFSET.load(Ordering::SeqCst)) as u64
+}
+
+#[derive(Options, Debug)]
+struct Args {
+    #[options(help = "display this message")]
+    help: bool,
+
+    #[options(help = "address to listen on", default = "127.0.0.1:8080")]
+    addr: String,
+
+    #[options(help = "pretend the server started this many seconds after the unix epoch")]
+    epoch: Option<u64>,
+}
+
+fn main() {
+    let args = Args::parse_args_default_or_exit();
+    if let Some(epoch) = args.epoch {
+        OFFSET.store(epoch as i64 - clock() as i64, Ordering::SeqCst);
+    }
+
+    let listener = TcpListener::bind(&args.addr).unwrap_or_else(|err| {
+        eprintln!("cannot listen on `{}` because: {}", args.addr, err);
+        std::process::exit(1);
+    });
+    let addr = listener.local_addr().unwrap();
+
+    eprintln!("listening on http://{}", addr);
+    eprintln!(
+        "use: twitchlink --api-url http://{0} --usher-url http://{0} <channel>",
+        addr
+    );
+
+    let server = std::sync::Arc::new(Server {
+        base: format!("http://{}", addr),
+        requests: Mutex::default(),
+    });
+    for stream in listener.incoming().flatten() {
+        let server = std::sync::Arc::clone(&server);
+        std::thread::spawn(move || {
+            if let Err(err) = server.handle(stream) {
+                eprintln!("error: {}", err);
+            }
+        });
+    }
+}
@@ -0,0 +1,481 @@
+use gumdrop::Options;
+use std::time::{Duration, Instant};
+
+use crate::{channel_name, find_stream, make_client, Abort, Quality};
+use twitchlink::hls::{Media, Segment};
+use twitchlink::{parse_streams, split_attributes, Client};
+
+#[derive(Options, Debug)]
+struct DebugArgs {
+    #[options(help = "display this message")]
+    help: bool,
+
+    #[options(command)]
+    command: Option<Command>,
+}
+
+#[derive(Options, Debug)]
+enum Command {
+    #[options(help = "poll the playlists of a channel and print what changes")]
+    WatchPlaylist(WatchArgs),
+}
+
+#[derive(Options, Debug)]
+struct WatchArgs {
+    #[options(help = "display this message")]
+    help: bool,
+
+    #[options(help = "seconds to wait between polls", default = "5")]
+    interval: u64,
+
+    #[options(help = "quality of the media playlist to watch")]
+    quality: Option<Quality>,
+
+    #[options(no_short, help = "don't color the output (or set `NO_COLOR`)")]
+    no_color: bool,
+
+    #[options(help = "log the requests being made to stderr")]
+    verbose: bool,
+
+    #[options(no_short, help = "don't mask tokens and signatures in logs and errors")]
+    log_unredacted: bool,
+
+    #[options(no_short, meta = "URL", help = "base url for the twitch api")]
+    api_url: Option<String>,
+
+    #[options(
+        no_short,
+        meta = "URL",
+        help = "base url for the usher playlist service"
+    )]
+    usher_url: Option<String>,
+
+    #[options(required, free, help = "the stream to watch")]
+    stream: String,
+}
+
+// the names of `Command`, so a channel called `debug` still works
+const COMMANDS: &[&str] = &["watch-playlist"];
+
+pub fn is_command(arg: &str) -> bool {
+    COMMANDS.contains(&arg)
+}
+
+pub fn run(argv: &[String]) {
+    let args =
+        DebugArgs::parse_args_default(argv).abort(|err| format!("twitchlink debug: {}", err));
+
+    if args.help_requested() || args.command.is_none() {
+        match args.command.as_ref().and_then(Options::command_name) {
+            Some(cmd) => {
+                println!("Usage: twitchlink debug {} [OPTIONS]", cmd);
+                println!();
+                println!("{}", DebugArgs::command_usage(cmd).unwrap_or_default());
+            }
+            None => {
+                println!("Usage: twitchlink debug COMMAND [OPTIONS]");
+                println!();
+                println!("Available commands:");
+                println!();
+                println!("{}", DebugArgs::command_list().unwrap_or_default());
+            }
+        }
+        return;
+    }
+
+    match args.command {
+        Some(Command::WatchPlaylist(args)) => watch_playlist(args),
+        None => unreachable!(),
+    }
+}
+
+struct Paint {
+    color: bool,
+}
+
+impl Paint {
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, s)
+        } else {
+            s.to_string()
+        }
+    }
+
+    fn added(&self, s: &str) -> String {
+        self.paint("32", &format!("+ {}", s))
+    }
+
+    fn removed(&self, s: &str) -> String {
+        self.paint("31", &format!("- {}", s))
+    }
+
+    fn event(&self, s: &str) -> String {
+        self.paint("33", &format!("! {}", s))
+    }
+
+    fn dim(&self, s: &str) -> String {
+        self.paint("2", s)
+    }
+}
+
+fn watch_playlist(args: WatchArgs) {
+    twitchlink::redact::set_unredacted(args.log_unredacted);
+    twitchlink::VERBOSE.store(args.verbose, std::sync::atomic::Ordering::SeqCst);
+
+    let paint = Paint {
+        color: !args.no_color && std::env::var_os("NO_COLOR").is_none(),
+    };
+    let channel = channel_name(&args.stream);
+    let quality = args.quality.clone().unwrap_or(Quality::Best);
+    let client = make_client(args.api_url.clone(), args.usher_url.clone());
+
+    let start = Instant::now();
+    let mut state = State::default();
+    loop {
+        let stamp = paint.dim(&format!("[+{}s]", start.elapsed().as_secs()));
+        match poll(&client, channel, &quality, &mut state, &paint) {
+            Ok(lines) if lines.is_empty() => {}
+            Ok(lines) => {
+                println!("{}", stamp);
+                lines.iter().for_each(|line| println!("  {}", line));
+            }
+            Err(err) => {
+                println!("{}", stamp);
+                println!("  {}", paint.event(&twitchlink::redact::redact(&err)));
+            }
+        }
+        std::thread::sleep(Duration::from_secs(args.interval.max(1)));
+    }
+}
+
+#[derive(Default)]
+struct State {
+    master: Vec<String>,
+    header: Vec<String>,
+    last_sequence: Option<u64>,
+    last_host: Option<String>,
+    variant: Option<String>,
+}
+
+fn poll(
+    client: &Client,
+    channel: &str,
+    quality: &Quality,
+    state: &mut State,
+    paint: &Paint,
+) -> Result<Vec<String>, String> {
+    let mut out = vec![];
+
+    let playlist = client
+        .fetch_playlist(channel)
+        .map_err(|err| err.to_string())?;
+
+    let master = playlist.lines().map(normalize).collect::<Vec<_>>();
+    for change in diff(&state.master, &master) {
+        out.push(match change {
+            Change::Added(line) => describe(paint.added(line), line, "new"),
+            Change::Removed(line) => describe(paint.removed(line), line, "removed"),
+        });
+    }
+    state.master = master;
+
+    let streams = parse_streams(channel, &playlist).map_err(|err| err.to_string())?;
+    let stream = find_stream(channel, &streams, quality)?;
+    if state.variant.as_ref() != Some(&stream.ty) {
+        out.push(paint.event(&format!("watching the `{}` media playlist", stream.ty)));
+        state.variant.replace(stream.ty.clone());
+    }
+
+    let media = client
+        .fetch_media(&stream.link)
+        .map_err(|err| err.to_string())?;
+    let media = Media::parse(&media);
+
+    // these change on every refresh
+    const VOLATILE: &[&str] = &[
+        "#EXT-X-MEDIA-SEQUENCE",
+        "#EXT-X-TWITCH-ELAPSED-SECS",
+        "#EXT-X-TWITCH-TOTAL-SECS",
+    ];
+    let header = media
+        .header
+        .iter()
+        .filter(|line| !VOLATILE.iter().any(|tag| line.starts_with(tag)))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for change in diff(&state.header, &header) {
+        out.push(match change {
+            Change::Added(line) => paint.added(line),
+            Change::Removed(line) => paint.removed(line),
+        });
+    }
+    state.header = header;
+
+    if let Some((last, first)) = state
+        .last_sequence
+        .filter(|&last| media.is_reset(last))
+        .zip(media.segments.first())
+    {
+        out.push(paint.event(&format!("sequence reset: {} -> {}", last, first.sequence)));
+        state.last_sequence.take();
+    }
+
+    let segments = media
+        .segments
+        .iter()
+        .filter(|seg| state.last_sequence.is_none_or(|last| seg.sequence > last))
+        .collect::<Vec<_>>();
+
+    if let (Some(first), Some(last)) = (segments.first(), segments.last()) {
+        out.push(paint.dim(&format!(
+            "{} new segment(s), sequence {}..={}",
+            segments.len(),
+            first.sequence,
+            last.sequence
+        )));
+    }
+
+    for seg in &segments {
+        for event in segment_events(seg, &mut state.last_host) {
+            out.push(paint.event(&event))
+        }
+    }
+
+    if let Some(last) = segments.last() {
+        state.last_sequence.replace(last.sequence);
+    }
+
+    Ok(out)
+}
+
+// the master playlist is fetched with a new token every time, so drop what always changes
+fn normalize(line: &str) -> String {
+    const VOLATILE: &[&str] = &[
+        "SERVER-TIME",
+        "STREAM-TIME",
+        "SERVING-ID",
+        "USER-IP",
+        "VIDEO-SESSION-ID",
+    ];
+
+    if let Some(info) = line.strip_prefix("#EXT-X-TWITCH-INFO:") {
+        let attrs = split_attributes(info)
+            .into_iter()
+            .filter(|attr| {
+                !VOLATILE
+                    .iter()
+                    .any(|key| attr.starts_with(&format!("{}=", key)))
+            })
+            .collect::<Vec<_>>();
+        return format!("#EXT-X-TWITCH-INFO:{}", attrs.join(","));
+    }
+
+    if !line.starts_with('#') {
+        return format!("{}/...", host(line));
+    }
+
+    line.to_string()
+}
+
+fn describe(painted: String, line: &str, what: &str) -> String {
+    let kind = if line.starts_with("#EXT-X-STREAM-INF") || line.starts_with("#EXT-X-MEDIA:") {
+        "variant"
+    } else if line.starts_with("#EXT-X-TWITCH-INFO") {
+        "node info"
+    } else {
+        return painted;
+    };
+    format!("{}  ({} {})", painted, what, kind)
+}
+
+// the interesting tags of a segment, and if it came from another node than the last one
+fn segment_events(seg: &Segment, last_host: &mut Option<String>) -> Vec<String> {
+    let mut out = seg
+        .tags
+        .iter()
+        .filter_map(|tag| {
+            classify(tag).map(|event| format!("#{} {}: {}", seg.sequence, event, tag))
+        })
+        .collect::<Vec<_>>();
+
+    let host = host(&seg.uri).to_string();
+    match last_host.as_ref() {
+        Some(last) if *last != host => out.push(format!(
+            "#{} node switch: {} -> {}",
+            seg.sequence, last, host
+        )),
+        _ => {}
+    }
+    last_host.replace(host);
+    out
+}
+
+fn classify(tag: &str) -> Option<&'static str> {
+    if tag.starts_with("#EXT-X-DISCONTINUITY") {
+        return Some("discontinuity");
+    }
+    if tag.starts_with("#EXT-X-DATERANGE") && tag.contains("stitched-ad") {
+        return Some("ad marker");
+    }
+    if tag.starts_with("#EXT-X-CUE-OUT") || tag.starts_with("#EXT-X-CUE-IN") {
+        return Some("ad marker");
+    }
+    if tag.starts_with("#EXT-X-DATERANGE") {
+        return Some("daterange");
+    }
+    if let Some(info) = tag.strip_prefix("#EXTINF:") {
+        let title = info
+            .split_once(',')
+            .map(|(_, title)| title)
+            .unwrap_or_default();
+        if !title.is_empty() && title != "live" {
+            return Some("segment title");
+        }
+    }
+    None
+}
+
+fn host(uri: &str) -> &str {
+    let rest = uri.split_once("://").map(|(_, rest)| rest).unwrap_or(uri);
+    rest.split('/').next().unwrap_or(rest)
+}
+
+#[derive(Debug, PartialEq)]
+enum Change<'a> {
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+// a plain lcs diff, the playlists are small enough for this
+fn diff<'a>(old: &'a [String], new: &'a [String]) -> Vec<Change<'a>> {
+    let (n, m) = (old.len(), new.len());
+    let mut table = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            }
+        }
+    }
+
+    let (mut i, mut j, mut out) = (0, 0, vec![]);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            out.push(Change::Removed(&old[i]));
+            i += 1;
+        } else {
+            out.push(Change::Added(&new[j]));
+            j += 1;
+        }
+    }
+    out.extend(old[i..].iter().map(|s| Change::Removed(s)));
+    out.extend(new[j..].iter().map(|s| Change::Added(s)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.split_whitespace().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn diff_lines() {
+        let (old, new) = (lines("a b c d"), lines("a c d e"));
+        assert_eq!(
+            diff(&old, &new),
+            vec![Change::Removed("b"), Change::Added("e")]
+        );
+
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!(
+            diff(&[], &lines("a b")),
+            vec![Change::Added("a"), Change::Added("b")]
+        );
+        assert_eq!(
+            diff(&lines("a"), &lines("b")),
+            vec![Change::Removed("a"), Change::Added("b")]
+        );
+    }
+
+    #[test]
+    fn normalize_drops_volatile_attributes() {
+        assert_eq!(
+            normalize(
+                r#"#EXT-X-TWITCH-INFO:NODE="video-edge-1",SERVER-TIME="1.00",USER-IP="127.0.0.1",CLUSTER="local""#
+            ),
+            r#"#EXT-X-TWITCH-INFO:NODE="video-edge-1",CLUSTER="local""#
+        );
+        assert_eq!(
+            normalize("https://video-weaver.local/v1/playlist/abc.m3u8?token=1"),
+            "video-weaver.local/..."
+        );
+        assert_eq!(
+            normalize("#EXT-X-STREAM-INF:BANDWIDTH=1"),
+            "#EXT-X-STREAM-INF:BANDWIDTH=1"
+        );
+    }
+
+    #[test]
+    fn classify_tags() {
+        assert_eq!(classify("#EXT-X-DISCONTINUITY"), Some("discontinuity"));
+        assert_eq!(
+            classify(r#"#EXT-X-DATERANGE:ID="stitched-ad-1",CLASS="twitch-stitched-ad""#),
+            Some("ad marker")
+        );
+        assert_eq!(classify("#EXT-X-CUE-OUT:30"), Some("ad marker"));
+        assert_eq!(
+            classify(r#"#EXT-X-DATERANGE:ID="source-1",CLASS="twitch-session""#),
+            Some("daterange")
+        );
+        assert_eq!(classify("#EXTINF:2.000,Amazon"), Some("segment title"));
+        assert_eq!(classify("#EXTINF:2.000,live"), None);
+        assert_eq!(classify("#EXTINF:2.000,"), None);
+        assert_eq!(classify("#EXT-X-PROGRAM-DATE-TIME:2020-01-01"), None);
+    }
+
+    #[test]
+    fn node_switches() {
+        let media = Media::parse(
+            "#EXTM3U
+#EXT-X-MEDIA-SEQUENCE:7
+#EXTINF:2.000,live
+https://edge-1.local/a.ts
+#EXT-X-DISCONTINUITY
+#EXTINF:2.000,Amazon
+https://edge-2.local/b.ts
+#EXTINF:2.000,live
+https://edge-2.local/c.ts
+",
+        );
+
+        let mut last = None;
+        let events = media
+            .segments
+            .iter()
+            .map(|seg| segment_events(seg, &mut last))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                vec![],
+                vec![
+                    "#8 discontinuity: #EXT-X-DISCONTINUITY".to_string(),
+                    "#8 segment title: #EXTINF:2.000,Amazon".to_string(),
+                    "#8 node switch: edge-1.local -> edge-2.local".to_string(),
+                ],
+                vec![],
+            ]
+        );
+        assert_eq!(last.as_deref(), Some("edge-2.local"));
+    }
+}
@@ -0,0 +1,162 @@
+pub struct Segment {
+    pub sequence: u64,
+    pub duration: f64,
+    pub tags: Vec<String>,
+    pub uri: String,
+}
+
+pub struct Media {
+    pub header: Vec<String>,
+    pub segments: Vec<Segment>,
+    pub target_duration: Option<f64>,
+    pub ended: bool,
+}
+
+impl Media {
+    pub fn parse(playlist: &str) -> Self {
+        let mut sequence = playlist
+            .lines()
+            .find_map(|line| line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:"))
+            .and_then(|seq| seq.trim().parse().ok())
+            .unwrap_or(0);
+
+        let (mut header, mut segments, mut tags) = (vec![], vec![], vec![]);
+        let (mut target_duration, mut ended) = (None, false);
+
+        for line in playlist.lines().map(str::trim).filter(|s| !s.is_empty()) {
+            if !line.starts_with('#') {
+                let duration = tags
+                    .iter()
+                    .find_map(|tag: &String| tag.strip_prefix("#EXTINF:"))
+                    .and_then(|info| info.split(',').next())
+                    .and_then(|secs| secs.trim().parse().ok())
+                    .unwrap_or_default();
+
+                segments.push(Segment {
+                    sequence,
+                    duration,
+                    tags: std::mem::take(&mut tags),
+                    uri: line.to_string(),
+                });
+                sequence += 1;
+                continue;
+            }
+
+            if let Some(secs) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                target_duration = secs.trim().parse().ok();
+            }
+            if line.starts_with("#EXT-X-ENDLIST") {
+                ended = true;
+            }
+
+            if segments.is_empty() && !is_segment_tag(line) {
+                header.push(line.to_string());
+                continue;
+            }
+            tags.push(line.to_string());
+        }
+
+        Self {
+            header,
+            segments,
+            target_duration,
+            ended,
+        }
+    }
+
+    // whether the playlist went back to before the `last` sequence seen, which happens
+    // when the stream restarts. the new segments can't be lined up with the old ones
+    pub fn is_reset(&self, last: u64) -> bool {
+        self.segments.last().is_some_and(|seg| seg.sequence < last)
+    }
+}
+
+fn is_segment_tag(tag: &str) -> bool {
+    const TAGS: &[&str] = &[
+        "#EXTINF",
+        "#EXT-X-DISCONTINUITY",
+        "#EXT-X-DATERANGE",
+        "#EXT-X-PROGRAM-DATE-TIME",
+        "#EXT-X-CUE-OUT",
+        "#EXT-X-CUE-IN",
+        "#EXT-X-BYTERANGE",
+        "#EXT-X-KEY",
+        "#EXT-X-MAP",
+    ];
+    TAGS.iter().any(|t| tag.starts_with(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let media = Media::parse(
+            "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:100
+#EXT-X-TWITCH-ELAPSED-SECS:10.000
+
+#EXT-X-PROGRAM-DATE-TIME:2020-01-01T00:00:00.000Z
+#EXTINF:2.002,live
+https://edge.local/100.ts
+#EXT-X-DISCONTINUITY
+#EXTINF:1.5,Amazon
+/101.ts
+#EXT-X-ENDLIST
+",
+        );
+
+        assert_eq!(
+            media.header,
+            [
+                "#EXTM3U",
+                "#EXT-X-VERSION:3",
+                "#EXT-X-TARGETDURATION:6",
+                "#EXT-X-MEDIA-SEQUENCE:100",
+                "#EXT-X-TWITCH-ELAPSED-SECS:10.000",
+            ]
+        );
+        assert_eq!(media.target_duration, Some(6.0));
+        assert!(media.ended);
+
+        let seg = &media.segments;
+        assert_eq!(seg.len(), 2);
+        assert_eq!((seg[0].sequence, seg[0].duration), (100, 2.002));
+        assert_eq!(
+            seg[0].tags,
+            [
+                "#EXT-X-PROGRAM-DATE-TIME:2020-01-01T00:00:00.000Z",
+                "#EXTINF:2.002,live"
+            ]
+        );
+        assert_eq!(seg[0].uri, "https://edge.local/100.ts");
+        assert_eq!((seg[1].sequence, seg[1].duration), (101, 1.5));
+        assert_eq!(seg[1].tags, ["#EXT-X-DISCONTINUITY", "#EXTINF:1.5,Amazon"]);
+        assert_eq!(seg[1].uri, "/101.ts");
+    }
+
+    #[test]
+    fn reset() {
+        let media = Media::parse("#EXT-X-MEDIA-SEQUENCE:5\n#EXTINF:2,\na.ts\n#EXTINF:2,\nb.ts\n");
+        assert!(!media.is_reset(4));
+        assert!(!media.is_reset(6));
+        assert!(media.is_reset(7));
+        assert!(!Media::parse("#EXTM3U\n").is_reset(7));
+    }
+
+    #[test]
+    fn parse_without_a_sequence() {
+        let media = Media::parse("#EXTM3U\n#EXTINF:2,\na.ts\n#EXTINF:2,\nb.ts\n");
+        let sequences = media
+            .segments
+            .iter()
+            .map(|seg| seg.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [0, 1]);
+        assert_eq!(media.target_duration, None);
+        assert!(!media.ended);
+    }
+}
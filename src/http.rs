@@ -0,0 +1,779 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// how long a connection can sit in the pool before it's dropped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// how many idle connections are kept for each host
+const MAX_IDLE: usize = 4;
+const TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+// the largest body that'll be read, segments are a few megabytes at most
+const MAX_BODY: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum Error {
+    Url(&'static str),
+    UnsupportedScheme(String),
+    Io(std::io::Error),
+    Tls(String),
+    InvalidResponse(&'static str),
+    TooManyRedirects,
+    Json(serde_json::Error),
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Url(err) => write!(f, "invalid url: {}", err),
+            Error::UnsupportedScheme(scheme) => write!(f, "unsupported scheme `{}`", scheme),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Tls(err) => write!(f, "tls error: {}", err),
+            Error::InvalidResponse(what) => write!(f, "invalid response: {}", what),
+            Error::TooManyRedirects => write!(f, "too many redirects"),
+            Error::Json(err) => write!(f, "invalid json: {}", err),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+// appends the (encoded) query parameters to the url
+pub fn with_params(url: &str, params: &[(&str, &str)]) -> Result<String, Error> {
+    let mut url = Url::parse(url)?;
+    for (name, value) in params {
+        if !url.target.contains('?') {
+            url.target.push('?')
+        } else if !url.target.ends_with(['?', '&']) {
+            url.target.push('&')
+        }
+        url.target.push_str(&encode(name));
+        url.target.push('=');
+        url.target.push_str(&encode(value));
+    }
+    Ok(url.to_string())
+}
+
+// percent-encodes everything but the unreserved characters
+fn encode(s: &str) -> String {
+    s.bytes().fold(String::new(), |mut out, b| {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+        out
+    })
+}
+
+// an absolute http or https url. the host has no brackets, so it can be used for
+// connecting, and the target is the path and the query
+#[derive(Debug, Clone, PartialEq)]
+struct Url {
+    scheme: String,
+    host: String,
+    port: u16,
+    target: String,
+}
+
+impl Url {
+    fn parse(input: &str) -> Result<Self, Error> {
+        let (scheme, rest) = input
+            .split_once("://")
+            .ok_or(Error::Url("expected an absolute url"))?;
+        let scheme = scheme.to_ascii_lowercase();
+        let default = match scheme.as_str() {
+            "http" => 80,
+            "https" => 443,
+            _ => return Err(Error::UnsupportedScheme(scheme)),
+        };
+
+        // the fragment is never sent
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, ""),
+        };
+        let target = match target {
+            "" => "/".to_string(),
+            t if t.starts_with('?') => format!("/{}", t),
+            t => t.to_string(),
+        };
+        if target.bytes().any(|b| b.is_ascii_control() || b == b' ') {
+            return Err(Error::Url("the path cannot contain spaces"));
+        }
+
+        // credentials in the url are ignored
+        let authority = authority.rsplit('@').next().unwrap_or_default();
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(v6) => {
+                let (host, rest) = v6
+                    .split_once(']')
+                    .ok_or(Error::Url("unclosed `[` in the host"))?;
+                match rest {
+                    "" => (host, None),
+                    port => (
+                        host,
+                        Some(port.strip_prefix(':').ok_or(Error::Url("bad port"))?),
+                    ),
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+
+        if host.is_empty() {
+            return Err(Error::Url("empty host"));
+        }
+        if host.contains(['/', '\\', '@', '[', ']', ' ']) {
+            return Err(Error::Url("invalid host"));
+        }
+        let port = match port {
+            Some("") | None => default,
+            Some(port) => port.parse().map_err(|_| Error::Url("bad port"))?,
+        };
+
+        Ok(Self {
+            scheme,
+            host: host.to_ascii_lowercase(),
+            port,
+            target,
+        })
+    }
+
+    // resolves a redirect's location against this url
+    fn join(&self, location: &str) -> Result<Self, Error> {
+        if location.contains("://") {
+            return Self::parse(location);
+        }
+        if location.starts_with("//") {
+            return Self::parse(&format!("{}:{}", self.scheme, location));
+        }
+
+        let location = location.split('#').next().unwrap_or_default();
+        let target = if location.starts_with('/') {
+            location.to_string()
+        } else if location.starts_with('?') {
+            let path = self.target.split('?').next().unwrap_or_default();
+            format!("{}{}", path, location)
+        } else {
+            let path = self.target.split('?').next().unwrap_or_default();
+            let dir = &path[..path.rfind('/').map_or(0, |pos| pos + 1)];
+            format!("{}{}", dir, location)
+        };
+        Self::parse(&format!("{}://{}{}", self.scheme, self.authority(), target))
+    }
+
+    fn is_default_port(&self) -> bool {
+        matches!(
+            (self.scheme.as_str(), self.port),
+            ("http", 80) | ("https", 443)
+        )
+    }
+
+    // the host (bracketed if it's ipv6) and the port, if it isn't the default one
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.is_default_port() {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    fn key(&self) -> Key {
+        (self.scheme.clone(), self.host.clone(), self.port)
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}{}", self.scheme, self.authority(), self.target)
+    }
+}
+
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn bytes(self) -> Vec<u8> {
+        self.body
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.body).map_err(Error::Json)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<native_tls::TlsStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+type Conn = BufReader<Stream>;
+
+// scheme, host and port
+type Key = (String, String, u16);
+
+// keep-alive connections, shared by everything that uses the pool. a connection
+// is taken out of the pool for a request, and put back once the body is read.
+#[derive(Default)]
+pub struct Pool {
+    idle: Mutex<HashMap<Key, Vec<(Conn, Instant)>>>,
+    tls: Mutex<Option<native_tls::TlsConnector>>,
+}
+
+impl Pool {
+    pub fn get(&self, url: &str, mut headers: &[(&str, &str)]) -> Result<Response, Error> {
+        let mut url = Url::parse(url)?;
+        let origin = url.key();
+        for _ in 0..=MAX_REDIRECTS {
+            let resp = self.send(&url, headers)?;
+            match (resp.status, resp.header("Location")) {
+                (301 | 302 | 303 | 307 | 308, Some(location)) => url = url.join(location)?,
+                _ => return Ok(resp),
+            }
+
+            // the headers carry credentials, so they only go to where they were meant for
+            if url.key() != origin && !headers.is_empty() {
+                verbose!("redirected to another origin, not sending the headers to it");
+                headers = &[];
+            }
+        }
+        Err(Error::TooManyRedirects)
+    }
+
+    fn send(&self, url: &Url, headers: &[(&str, &str)]) -> Result<Response, Error> {
+        let key = url.key();
+
+        // the server could have closed an idle connection, so retry those on a new one
+        if let Some(conn) = self.take(&key) {
+            verbose!("reusing a connection to {}:{}", key.1, key.2);
+            match self.exchange(conn, &key, url, headers) {
+                Err(Error::Io(..)) => {}
+                res => return res,
+            }
+        }
+
+        let conn = self.connect(&key)?;
+        self.exchange(conn, &key, url, headers)
+    }
+
+    fn exchange(
+        &self,
+        mut conn: Conn,
+        key: &Key,
+        url: &Url,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, Error> {
+        let mut req = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\n",
+            url.target,
+            url.authority()
+        );
+        for (name, value) in headers {
+            req.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        req.push_str(concat!(
+            "User-Agent: twitchlink/",
+            env!("CARGO_PKG_VERSION"),
+            "\r\nAccept: */*\r\nConnection: keep-alive\r\n\r\n"
+        ));
+
+        let stream = conn.get_mut();
+        stream.write_all(req.as_bytes())?;
+        stream.flush()?;
+
+        let (resp, reusable) = read_response(&mut conn)?;
+        if reusable {
+            self.put(key, conn)
+        }
+        Ok(resp)
+    }
+
+    fn connect(&self, (scheme, host, port): &Key) -> Result<Conn, Error> {
+        let tcp = connect(host, *port)?;
+        tcp.set_read_timeout(Some(TIMEOUT))?;
+        tcp.set_write_timeout(Some(TIMEOUT))?;
+        tcp.set_nodelay(true)?;
+
+        let stream = match scheme.as_str() {
+            "https" => Stream::Tls(Box::new(
+                self.tls()?
+                    .connect(host, tcp)
+                    .map_err(|err| Error::Tls(err.to_string()))?,
+            )),
+            _ => Stream::Plain(tcp),
+        };
+        Ok(BufReader::new(stream))
+    }
+
+    fn tls(&self) -> Result<native_tls::TlsConnector, Error> {
+        let mut tls = lock(&self.tls);
+        if let Some(tls) = tls.as_ref() {
+            return Ok(tls.clone());
+        }
+        let connector =
+            native_tls::TlsConnector::new().map_err(|err| Error::Tls(err.to_string()))?;
+        Ok(tls.get_or_insert(connector).clone())
+    }
+
+    fn take(&self, key: &Key) -> Option<Conn> {
+        let mut idle = lock(&self.idle);
+        let conns = idle.get_mut(key)?;
+        while let Some((conn, since)) = conns.pop() {
+            if since.elapsed() < IDLE_TIMEOUT {
+                return Some(conn);
+            }
+        }
+        None
+    }
+
+    fn put(&self, key: &Key, conn: Conn) {
+        let mut idle = lock(&self.idle);
+        let conns = idle.entry(key.clone()).or_default();
+        conns.retain(|(_, since)| since.elapsed() < IDLE_TIMEOUT);
+        if conns.len() < MAX_IDLE {
+            conns.push((conn, Instant::now()))
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(err) => err.into_inner(),
+    }
+}
+
+// tries each of the host's addresses in turn
+fn connect(host: &str, port: u16) -> std::io::Result<TcpStream> {
+    let mut last = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(tcp) => return Ok(tcp),
+            Err(err) => last.replace(err),
+        };
+    }
+    Err(last.unwrap_or_else(|| std::io::ErrorKind::AddrNotAvailable.into()))
+}
+
+// the response, and whether the connection can be used again
+fn read_response(conn: &mut impl BufRead) -> Result<(Response, bool), Error> {
+    let line = read_line(conn)?;
+    let mut parts = line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(Error::InvalidResponse("bad status line"))?;
+    if !version.starts_with("HTTP/1.") {
+        return Err(Error::InvalidResponse("bad status line"));
+    }
+
+    let mut headers = vec![];
+    loop {
+        let line = read_line(conn)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()))
+        }
+    }
+
+    let mut resp = Response {
+        status,
+        headers,
+        body: vec![],
+    };
+
+    let mut reusable = match resp.header("Connection") {
+        Some(conn) if conn.eq_ignore_ascii_case("close") => false,
+        Some(conn) if conn.eq_ignore_ascii_case("keep-alive") => true,
+        _ => version == "HTTP/1.1",
+    };
+
+    let chunked = resp
+        .header("Transfer-Encoding")
+        .is_some_and(|te| te.eq_ignore_ascii_case("chunked"));
+    let length = resp
+        .header("Content-Length")
+        .and_then(|len| len.parse::<usize>().ok());
+
+    resp.body = if chunked {
+        read_chunked(conn)?
+    } else if let Some(length) = length {
+        if length > MAX_BODY {
+            return Err(Error::InvalidResponse("body is too large"));
+        }
+        // the length isn't trusted, so read it rather than allocating it up front
+        let mut body = vec![];
+        conn.take(length as u64).read_to_end(&mut body)?;
+        if body.len() != length {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        body
+    } else if status == 204 || status == 304 {
+        vec![]
+    } else {
+        // the body ends when the connection does
+        reusable = false;
+        let mut body = vec![];
+        conn.take(MAX_BODY as u64 + 1).read_to_end(&mut body)?;
+        if body.len() > MAX_BODY {
+            return Err(Error::InvalidResponse("body is too large"));
+        }
+        body
+    };
+
+    Ok((resp, reusable))
+}
+
+fn read_chunked(conn: &mut impl BufRead) -> Result<Vec<u8>, Error> {
+    let mut body = vec![];
+    loop {
+        let line = read_line(conn)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| Error::InvalidResponse("bad chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        let start = body.len();
+        let end = start
+            .checked_add(size)
+            .filter(|&end| end <= MAX_BODY)
+            .ok_or(Error::InvalidResponse("body is too large"))?;
+        conn.take(size as u64).read_to_end(&mut body)?;
+        if body.len() != end {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        read_line(conn)?;
+    }
+    // skip any trailers
+    while !read_line(conn)?.is_empty() {}
+    Ok(body)
+}
+
+fn read_line(conn: &mut impl BufRead) -> Result<String, Error> {
+    let mut line = vec![];
+    if conn.read_until(b'\n', &mut line)? == 0 {
+        return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    // answers requests with `responses` in order, and returns the requests it got
+    fn serve(
+        listener: TcpListener,
+        responses: Vec<String>,
+    ) -> std::thread::JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let mut responses = responses.into_iter();
+            let mut requests = vec![];
+            for stream in listener.incoming() {
+                let mut conn = BufReader::new(stream.unwrap());
+                loop {
+                    let mut req = String::new();
+                    while let Ok(line) = read_line(&mut conn) {
+                        if line.is_empty() {
+                            break;
+                        }
+                        req.push_str(&line);
+                        req.push('\n');
+                    }
+                    if req.is_empty() {
+                        break;
+                    }
+                    requests.push(req);
+                    match responses.next() {
+                        Some(resp) => conn.get_mut().write_all(resp.as_bytes()).unwrap(),
+                        None => return requests,
+                    }
+                    if responses.len() == 0 {
+                        return requests;
+                    }
+                }
+            }
+            requests
+        })
+    }
+
+    fn listen(addr: &str) -> (TcpListener, String) {
+        let listener = TcpListener::bind(addr).unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, format!("http://{}", addr))
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+    fn redirect(location: &str) -> String {
+        format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+            location
+        )
+    }
+
+    #[test]
+    fn parse_url() {
+        let url = Url::parse("HTTPS://Example.com/a/b?c=d#frag").unwrap();
+        assert_eq!(
+            url,
+            Url {
+                scheme: "https".into(),
+                host: "example.com".into(),
+                port: 443,
+                target: "/a/b?c=d".into(),
+            }
+        );
+        assert_eq!(url.to_string(), "https://example.com/a/b?c=d");
+
+        let url = Url::parse("http://user:pass@[::1]:8080?x").unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.target, "/?x");
+        assert_eq!(url.authority(), "[::1]:8080");
+
+        assert!(matches!(
+            Url::parse("ftp://example.com"),
+            Err(Error::UnsupportedScheme(..))
+        ));
+        for bad in &[
+            "example.com",
+            "http://",
+            "http://:80/",
+            "http://[::1/",
+            "http://host:port/",
+            "http://host/a b",
+            "http://host/a\r\nb",
+        ] {
+            assert!(matches!(Url::parse(bad), Err(Error::Url(..))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn join_url() {
+        let url = Url::parse("http://host:8080/a/b?c").unwrap();
+        let join = |location| url.join(location).unwrap().to_string();
+        assert_eq!(join("https://other/x"), "https://other/x");
+        assert_eq!(join("//other/x"), "http://other/x");
+        assert_eq!(join("/x?y"), "http://host:8080/x?y");
+        assert_eq!(join("x"), "http://host:8080/a/x");
+        assert_eq!(join("?d"), "http://host:8080/a/b?d");
+    }
+
+    #[test]
+    fn params() {
+        assert_eq!(
+            with_params("http://host/path", &[("a", "1 2"), ("b", "x&y=z/é")]).unwrap(),
+            "http://host/path?a=1%202&b=x%26y%3Dz%2F%C3%A9"
+        );
+        assert_eq!(
+            with_params("http://host/path?first=1", &[("a", "-._~")]).unwrap(),
+            "http://host/path?first=1&a=-._~"
+        );
+    }
+
+    #[test]
+    fn redirect_keeps_headers_on_the_same_origin() {
+        let (listener, base) = listen("127.0.0.1:0");
+        let server = serve(listener, vec![redirect("/next"), OK.to_string()]);
+
+        let resp = Pool::default()
+            .get(&base, &[("Authorization", "OAuth secret")])
+            .unwrap();
+        assert_eq!(resp.text(), "ok");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("GET /next HTTP/1.1\n"));
+        assert!(requests[1].contains("Authorization: OAuth secret"));
+    }
+
+    #[test]
+    fn redirect_drops_headers_on_another_origin() {
+        let (other, other_base) = listen("127.0.0.1:0");
+        let other = serve(other, vec![OK.to_string()]);
+
+        let (listener, base) = listen("127.0.0.1:0");
+        let location = format!("{}/elsewhere", other_base);
+        let server = serve(listener, vec![redirect(&location)]);
+
+        let resp = Pool::default()
+            .get(&base, &[("Authorization", "OAuth secret")])
+            .unwrap();
+        assert_eq!(resp.text(), "ok");
+
+        assert!(server.join().unwrap()[0].contains("Authorization"));
+        let requests = other.join().unwrap();
+        assert!(requests[0].starts_with("GET /elsewhere HTTP/1.1\n"));
+        assert!(!requests[0].contains("Authorization"), "{}", requests[0]);
+    }
+
+    #[test]
+    fn ipv6() {
+        // not every machine has ipv6
+        let listener = match TcpListener::bind("[::1]:0") {
+            Ok(listener) => listener,
+            Err(..) => return,
+        };
+        let port = listener.local_addr().unwrap().port();
+        let server = serve(listener, vec![OK.to_string()]);
+
+        let resp = Pool::default()
+            .get(&format!("http://[::1]:{}/", port), &[])
+            .unwrap();
+        assert_eq!(resp.text(), "ok");
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains(&format!("Host: [::1]:{}\n", port)));
+    }
+
+    #[test]
+    fn too_large() {
+        let mut input = &b"HTTP/1.1 200 OK\r\nContent-Length: 99999999999999\r\n\r\nhello"[..];
+        assert!(matches!(
+            read_response(&mut input),
+            Err(Error::InvalidResponse("body is too large"))
+        ));
+
+        let mut input =
+            &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\nffffffffffffffff\r\n"[..];
+        assert!(matches!(
+            read_response(&mut input),
+            Err(Error::InvalidResponse("body is too large"))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut input = &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello"[..];
+        assert!(matches!(read_response(&mut input), Err(Error::Io(..))));
+
+        let mut input = &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\na\r\nhello"[..];
+        assert!(matches!(read_response(&mut input), Err(Error::Io(..))));
+    }
+
+    #[test]
+    fn content_length() {
+        let mut input = &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1"[..];
+        let (resp, reusable) = read_response(&mut input).unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.bytes(), b"hello");
+        assert!(reusable);
+        // the next response is left alone
+        assert_eq!(input, b"HTTP/1.1");
+    }
+
+    #[test]
+    fn chunked() {
+        let mut input = &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nTrailer: yes\r\n\r\n"[..];
+        let (resp, reusable) = read_response(&mut input).unwrap();
+        assert_eq!(resp.text(), "hello, world");
+        assert!(reusable);
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn connection_close() {
+        let mut input =
+            &b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"[..];
+        let (resp, reusable) = read_response(&mut input).unwrap();
+        assert_eq!(resp.status(), 404);
+        assert!(!reusable);
+    }
+
+    #[test]
+    fn read_to_end() {
+        let mut input = &b"HTTP/1.0 200 OK\r\n\r\nuntil the end"[..];
+        let (resp, reusable) = read_response(&mut input).unwrap();
+        assert_eq!(resp.text(), "until the end");
+        assert!(!reusable);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut input = &b"SSH-2.0-OpenSSH\r\n\r\n"[..];
+        assert!(matches!(
+            read_response(&mut input),
+            Err(Error::InvalidResponse(..))
+        ));
+    }
+}
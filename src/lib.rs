@@ -0,0 +1,546 @@
+// fetches twitch stream playlists. this is the library behind the `twitchlink`
+// binary, a single `Client` can be shared between threads.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+pub mod redact;
+pub mod report;
+
+pub static VERBOSE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::VERBOSE.load(std::sync::atomic::Ordering::SeqCst) {
+            eprintln!("{}", $crate::redact::redact(&format!($($arg)*)));
+        }
+    };
+}
+
+pub mod hls;
+pub mod http;
+
+#[derive(Debug)]
+pub enum Error {
+    GetAccessToken(String, http::Error),
+    Deserialize(String, http::Error),
+    GetPlaylist(String, http::Error),
+    GetMediaPlaylist(String, http::Error),
+    GetSegment(String, http::Error),
+    GetFollowed(http::Error),
+    Status(String, u16),
+    Output(String, std::io::Error),
+    InvalidPlaylist(String),
+    FindToken(String),
+    FindSignature(String),
+    Forbidden(String),
+    FindUser,
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::GetAccessToken(_, err)
+            | Error::Deserialize(_, err)
+            | Error::GetPlaylist(_, err)
+            | Error::GetMediaPlaylist(_, err)
+            | Error::GetSegment(_, err)
+            | Error::GetFollowed(err) => Some(err),
+            Error::Output(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::GetAccessToken(channel, err) => write!(
+                f,
+                "cannot get access token for `{}` because: {}",
+                channel, err
+            ),
+            Error::Deserialize(channel, err) => write!(
+                f,
+                "cannot get deserialize response for `{}` because: {}",
+                channel, err
+            ),
+            Error::GetPlaylist(channel, err) => {
+                write!(f, "cannot get playlist for `{}` because: {}", channel, err)
+            }
+            Error::GetMediaPlaylist(url, err) => {
+                write!(f, "cannot get media playlist `{}` because: {}", url, err)
+            }
+            Error::GetSegment(url, err) => {
+                write!(f, "cannot get segment `{}` because: {}", url, err)
+            }
+            Error::GetFollowed(err) => {
+                write!(f, "cannot get followed channels because: {}", err)
+            }
+            Error::Status(url, status) => {
+                write!(f, "request to `{}` failed with status {}", url, status)
+            }
+            Error::Output(target, err) => {
+                write!(f, "cannot write to output `{}` because: {}", target, err)
+            }
+
+            Error::InvalidPlaylist(channel) => write!(f, "invalid player for `{}`", channel),
+
+            Error::FindToken(channel) => write!(f, "cannot find token for `{}`", channel),
+            Error::FindSignature(channel) => write!(f, "cannot find signature for `{}`", channel),
+            Error::Forbidden(channel) => {
+                write!(f, "access to the playlist for `{}` was denied", channel)
+            }
+            Error::FindUser => write!(f, "cannot find the user for the oauth token"),
+        }
+    }
+}
+
+const API_URL: &str = "https://api.twitch.tv";
+const USHER_URL: &str = "https://usher.ttvnw.net";
+
+// how long before it expires a cached token is considered stale, in seconds
+const TOKEN_MARGIN: u64 = 60;
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    token: String,
+    sig: String,
+    expires: Option<u64>,
+}
+
+impl AccessToken {
+    fn is_fresh(&self) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.expires
+            .is_some_and(|expires| expires > now + TOKEN_MARGIN)
+    }
+}
+
+// the client can be shared between threads. the token cache is only locked to
+// read or insert a token, never while a request is in flight. connections are
+// kept alive in a pool, so the threads reuse them as well.
+pub struct Client {
+    client_id: String,
+    api_url: String,
+    usher_url: String,
+    tokens: Mutex<HashMap<String, AccessToken>>,
+    http: http::Pool,
+}
+
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Client>();
+};
+
+impl Client {
+    pub fn new(id: impl ToString) -> Self {
+        Self {
+            client_id: id.to_string(),
+            api_url: API_URL.to_string(),
+            usher_url: USHER_URL.to_string(),
+            tokens: Mutex::new(HashMap::new()),
+            http: http::Pool::default(),
+        }
+    }
+
+    pub fn with_api_url(mut self, url: impl ToString) -> Self {
+        self.api_url = url.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_usher_url(mut self, url: impl ToString) -> Self {
+        self.usher_url = url.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn get(&self, channel: impl AsRef<str>) -> Result<Playlist, Error> {
+        let channel = channel.as_ref();
+        let playlist = self.fetch_playlist(channel)?;
+
+        parse_playlist(channel, &playlist)
+    }
+
+    pub fn fetch_playlist(&self, channel: &str) -> Result<String, Error> {
+        let cached = self.tokens().get(channel).filter(|t| t.is_fresh()).cloned();
+        let (token, cached) = match cached {
+            Some(token) => (token, true),
+            None => (self.fetch_token(channel)?, false),
+        };
+
+        match self.fetch_usher(channel, &token)? {
+            // the cached token could've been revoked, so try again with a new one
+            (403, ..) if cached => {
+                self.tokens().remove(channel);
+                let token = self.fetch_token(channel)?;
+                match self.fetch_usher(channel, &token)? {
+                    (403, ..) => Err(Error::Forbidden(channel.to_string())),
+                    (_, body) => Ok(body),
+                }
+            }
+            (403, ..) => Err(Error::Forbidden(channel.to_string())),
+            (_, body) => Ok(body),
+        }
+    }
+
+    fn fetch_token(&self, channel: &str) -> Result<AccessToken, Error> {
+        let url = format!("{}/api/channels/{}/access_token", self.api_url, channel);
+        let resp = self
+            .http
+            .get(&url, &[("Client-ID", &self.client_id)])
+            .map_err(|err| Error::GetAccessToken(channel.to_string(), err))?;
+        report::record(&url, resp.status());
+        verbose!("GET {} -> {}", url, resp.status());
+
+        let val: serde_json::Value = resp
+            .json()
+            .map_err(|err| Error::Deserialize(channel.to_string(), err))?;
+
+        let (token, sig) = match (
+            val.get("token").and_then(serde_json::Value::as_str),
+            val.get("sig").and_then(serde_json::Value::as_str),
+        ) {
+            (Some(token), Some(sig)) => (token, sig),
+            (None, ..) => return Err(Error::FindToken(channel.to_string())),
+            (.., None) => return Err(Error::FindSignature(channel.to_string())),
+        };
+        redact::add_secret(format!("token:{}", channel), token);
+        redact::add_secret(format!("sig:{}", channel), sig);
+        // it went out with the request, and this keeps it from being the oldest secret
+        redact::add_secret("client-id", &self.client_id);
+
+        // the token itself is json, and it says when it expires
+        let expires = serde_json::from_str::<serde_json::Value>(token)
+            .ok()
+            .and_then(|val| val.get("expires").and_then(serde_json::Value::as_u64));
+
+        let token = AccessToken {
+            token: token.to_string(),
+            sig: sig.to_string(),
+            expires,
+        };
+        if token.expires.is_some() {
+            self.tokens().insert(channel.to_string(), token.clone());
+        }
+        Ok(token)
+    }
+
+    fn fetch_usher(&self, channel: &str, token: &AccessToken) -> Result<(u16, String), Error> {
+        let url = format!("{}/api/channel/hls/{}.m3u8", self.usher_url, channel);
+        let resp = http::with_params(
+            &url,
+            &[
+                ("token", token.token.as_str()),
+                ("sig", token.sig.as_str()),
+                ("player_backend", "html5"),
+                ("player", "twitchweb"),
+                ("type", "any"),
+                ("allow_source", "true"),
+            ],
+        )
+        .and_then(|url| self.http.get(&url, &[]))
+        .map_err(|err| Error::GetPlaylist(channel.to_string(), err))?;
+        report::record(&url, resp.status());
+        verbose!(
+            "GET {}?token={}&sig={} -> {}",
+            url,
+            token.token,
+            token.sig,
+            resp.status()
+        );
+
+        Ok((resp.status(), resp.text()))
+    }
+
+    pub fn fetch_media(&self, url: &str) -> Result<String, Error> {
+        let resp = self
+            .http
+            .get(url, &[])
+            .map_err(|err| Error::GetMediaPlaylist(url.to_string(), err))?;
+        report::record(url, resp.status());
+        verbose!("GET {} -> {}", url, resp.status());
+
+        if !resp.is_success() {
+            return Err(Error::Status(url.to_string(), resp.status()));
+        }
+        Ok(resp.text())
+    }
+
+    pub fn fetch_segment(&self, url: &str) -> Result<Vec<u8>, Error> {
+        let resp = self
+            .http
+            .get(url, &[])
+            .map_err(|err| Error::GetSegment(url.to_string(), err))?;
+        report::record(url, resp.status());
+        verbose!("GET {} -> {}", url, resp.status());
+
+        if !resp.is_success() {
+            return Err(Error::Status(url.to_string(), resp.status()));
+        }
+        Ok(resp.bytes())
+    }
+
+    // the live channels followed by the owner of the oauth token
+    pub fn followed(&self, oauth: &str) -> Result<Vec<String>, Error> {
+        let oauth = oauth.trim_start_matches("oauth:");
+        redact::add_secret("oauth", oauth);
+        let auth = format!("Bearer {}", oauth);
+        let headers = [
+            ("Client-ID", self.client_id.as_str()),
+            ("Authorization", auth.as_str()),
+        ];
+
+        let url = format!("{}/helix/users", self.api_url);
+        let users: Helix<User> = self.fetch_helix(&url, &headers)?;
+        let user = users.data.into_iter().next().ok_or(Error::FindUser)?;
+
+        let url = format!("{}/helix/streams/followed", self.api_url);
+        let (mut live, mut cursor) = (vec![], None);
+        loop {
+            let mut params = vec![("user_id", user.id.as_str()), ("first", "100")];
+            if let Some(cursor) = cursor.as_deref() {
+                params.push(("after", cursor));
+            }
+            let url = http::with_params(&url, &params).map_err(Error::GetFollowed)?;
+            let page: Helix<FollowedStream> = self.fetch_helix(&url, &headers)?;
+            if page.data.is_empty() {
+                break;
+            }
+            live.extend(page.data.into_iter().map(|stream| stream.user_login));
+
+            cursor = page.pagination.and_then(|page| page.cursor);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(live)
+    }
+
+    fn fetch_helix<T>(&self, url: &str, headers: &[(&str, &str)]) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let resp = self.http.get(url, headers).map_err(Error::GetFollowed)?;
+        report::record(url, resp.status());
+        verbose!("GET {} -> {}", url, resp.status());
+
+        if !resp.is_success() {
+            return Err(Error::Status(url.to_string(), resp.status()));
+        }
+        resp.json().map_err(Error::GetFollowed)
+    }
+
+    fn tokens(&self) -> MutexGuard<'_, HashMap<String, AccessToken>> {
+        match self.tokens.lock() {
+            Ok(tokens) => tokens,
+            Err(err) => err.into_inner(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Helix<T> {
+    data: Vec<T>,
+    #[serde(default)]
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize)]
+struct Pagination {
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct FollowedStream {
+    user_login: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub streams: Vec<Stream>,
+    pub audio: Vec<AudioTrack>,
+}
+
+pub fn parse_playlist(channel: &str, playlist: &str) -> Result<Playlist, Error> {
+    Ok(Playlist {
+        streams: parse_streams(channel, playlist)?,
+        audio: parse_audio(playlist),
+    })
+}
+
+pub fn parse_streams(channel: &str, playlist: &str) -> Result<Vec<Stream>, Error> {
+    let mut map = HashMap::new();
+
+    // the attributes of the last #EXT-X-STREAM-INF, the uri follows on the next line
+    let mut info = None;
+
+    for line in playlist.lines() {
+        if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            info.replace(attributes(attrs));
+            continue;
+        }
+
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let attrs = match info.take() {
+            Some(attrs) => attrs,
+            None => continue,
+        };
+        let get = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        };
+
+        let quality = get("VIDEO").ok_or_else(|| Error::InvalidPlaylist(channel.to_string()))?;
+        let resolution = get("RESOLUTION").unwrap_or_default();
+        let bandwidth = get("BANDWIDTH").unwrap_or_default();
+        let audio = get("AUDIO");
+
+        let digits = quality
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(quality.len());
+
+        let s = match (quality.as_str(), quality[..digits].parse::<u32>()) {
+            ("chunked", ..) => Stream {
+                link: line.to_string(),
+                resolution,
+                bandwidth,
+                quality: None,
+                ty: "best".into(),
+                audio,
+            },
+            (.., Ok(n)) => Stream {
+                link: line.to_string(),
+                resolution,
+                bandwidth,
+                quality: Some(n),
+                ty: format!("{}p", n),
+                audio,
+            },
+            (s, ..) => {
+                verbose!("unknown quality: {}", s);
+                continue;
+            }
+        };
+
+        map.insert(s.quality, s);
+    }
+
+    let mut list = map.drain().map(|(_, v)| v).collect::<Vec<_>>();
+    list.sort_unstable_by(|a, b| match (a.quality, b.quality) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (None, ..) => std::cmp::Ordering::Less,
+        (.., None) => std::cmp::Ordering::Greater,
+    });
+    Ok(list)
+}
+
+pub fn parse_audio(playlist: &str) -> Vec<AudioTrack> {
+    playlist
+        .lines()
+        .filter_map(|line| line.strip_prefix("#EXT-X-MEDIA:"))
+        .map(attributes)
+        .filter(|attrs| attrs.contains(&("TYPE", "AUDIO")))
+        .filter_map(|attrs| {
+            let get = |key: &str| {
+                attrs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            };
+            Some(AudioTrack {
+                group: get("GROUP-ID")?,
+                name: get("NAME")?,
+                language: get("LANGUAGE"),
+                default: get("DEFAULT").is_some_and(|s| s == "YES"),
+                link: get("URI"),
+            })
+        })
+        .collect()
+}
+
+// splits on commas that aren't inside of quotes
+pub fn split_attributes(s: &str) -> Vec<&str> {
+    let (mut out, mut start, mut quoted) = (vec![], 0, false);
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                out.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&s[start..]);
+    out
+}
+
+fn attributes(s: &str) -> Vec<(&str, &str)> {
+    split_attributes(s)
+        .into_iter()
+        .filter_map(|attr| attr.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Stream {
+    pub resolution: String,
+    pub bandwidth: String,
+    pub link: String,
+    #[serde(skip)]
+    pub quality: Option<u32>,
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(rename = "audio_group", skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AudioTrack {
+    pub group: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub default: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl AudioTrack {
+    pub fn matches(&self, query: &str) -> bool {
+        self.name.eq_ignore_ascii_case(query)
+            || self.group.eq_ignore_ascii_case(query)
+            || self
+                .language
+                .as_ref()
+                .is_some_and(|lang| lang.eq_ignore_ascii_case(query))
+    }
+}
+
+impl std::fmt::Display for AudioTrack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.group, self.name)?;
+        if let Some(lang) = &self.language {
+            write!(f, " ({})", lang)?;
+        }
+        if self.default {
+            write!(f, " default")?;
+        }
+        Ok(())
+    }
+}
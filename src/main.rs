@@ -1,188 +1,11 @@
 use gumdrop::Options;
 use serde::Serialize;
-use std::collections::HashMap;
-
-#[derive(Debug)]
-enum Error {
-    GetAccessToken(String, attohttpc::Error),
-    Deserialize(String, attohttpc::Error),
-    GetPlaylist(String, attohttpc::Error),
-    GetResponseBody(String, attohttpc::Error),
-    InvalidPlaylist(String),
-    FindToken(String),
-    FindSignature(String),
-}
-
-impl std::error::Error for Error {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            Error::GetAccessToken(_, err)
-            | Error::Deserialize(_, err)
-            | Error::GetPlaylist(_, err)
-            | Error::GetResponseBody(_, err) => Some(err),
-            _ => None,
-        }
-    }
-}
-
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::GetAccessToken(channel, err) => write!(
-                f,
-                "cannot get access token for `{}` because: {}",
-                channel, err
-            ),
-            Error::Deserialize(channel, err) => write!(
-                f,
-                "cannot get deserialize response for `{}` because: {}",
-                channel, err
-            ),
-            Error::GetPlaylist(channel, err) => {
-                write!(f, "cannot get playlist for `{}` because: {}", channel, err)
-            }
-            Error::GetResponseBody(channel, err) => write!(
-                f,
-                "cannot get get response body for `{}` because: {}",
-                channel, err
-            ),
+use twitchlink::{redact, report, AudioTrack, Client, Error, Playlist, Stream, VERBOSE};
 
-            Error::InvalidPlaylist(channel) => write!(f, "invalid player for `{}`", channel),
-
-            Error::FindToken(channel) => write!(f, "cannot find token for `{}`", channel),
-            Error::FindSignature(channel) => write!(f, "cannot find signature for `{}`", channel),
-        }
-    }
-}
-
-struct Client {
-    client_id: String,
-}
-
-impl Client {
-    fn new(id: impl ToString) -> Self {
-        Self {
-            client_id: id.to_string(),
-        }
-    }
-
-    fn get(&self, channel: impl AsRef<str>) -> Result<Vec<Stream>, Error> {
-        let channel = channel.as_ref();
-        let playlist = self.fetch_playlist(channel)?;
-
-        let mut map = HashMap::new();
-
-        // why
-        let (mut quality, mut resolution, mut bandwidth) =
-            (String::new(), String::new(), String::new());
-
-        for line in playlist.lines() {
-            if line.contains("VIDEO=") {
-                let (index, _) = line
-                    .match_indices("VIDEO=")
-                    .next()
-                    .ok_or_else(|| Error::InvalidPlaylist(channel.to_string()))?;
-
-                quality = line[index + "VIDEO=".len()..].replace("\"", "");
-
-                let search = |q: &str| {
-                    let pos = line.find(q).unwrap();
-                    let end = (&line[pos..].find(',')).unwrap() + pos;
-                    &line[pos + q.len()..end]
-                };
-
-                bandwidth = search("BANDWIDTH=").to_string();
-                resolution = search("RESOLUTION=").to_string();
-            }
-
-            if quality.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            let s = match (quality.as_str(), quality[..3].parse::<u32>()) {
-                ("chunked", ..) => Stream {
-                    link: line.to_string(),
-                    resolution: std::mem::replace(&mut resolution, String::new()),
-                    bandwidth: std::mem::replace(&mut bandwidth, String::new()),
-                    quality: None,
-                    ty: "best".into(),
-                },
-                (.., Ok(n)) => Stream {
-                    link: line.to_string(),
-                    resolution: std::mem::replace(&mut resolution, String::new()),
-                    bandwidth: std::mem::replace(&mut bandwidth, String::new()),
-                    quality: Some(n),
-                    ty: format!("{}p", n),
-                },
-                (s, ..) => {
-                    eprintln!("WARN: unknown quality: {}", s);
-                    quality.clear();
-                    continue;
-                }
-            };
-
-            map.insert(s.quality, s);
-            quality.clear();
-        }
-
-        let mut list = map.drain().map(|(_, v)| v).collect::<Vec<_>>();
-        list.sort_unstable_by(|a, b| match (a.quality, b.quality) {
-            (Some(a), Some(b)) => b.cmp(&a),
-            (None, ..) => std::cmp::Ordering::Less,
-            (.., None) => std::cmp::Ordering::Greater,
-        });
-        Ok(list)
-    }
-
-    fn fetch_playlist(&self, channel: &str) -> Result<String, Error> {
-        let val: serde_json::Value = attohttpc::get(format!(
-            "https://api.twitch.tv/api/channels/{}/access_token",
-            channel
-        ))
-        .header("Client-ID", self.client_id.clone())
-        .send()
-        .map_err(|err| Error::GetAccessToken(channel.to_string(), err))?
-        .json()
-        .map_err(|err| Error::Deserialize(channel.to_string(), err))?;
-
-        let (token, sig) = match (
-            val.get("token").and_then(serde_json::Value::as_str),
-            val.get("sig").and_then(serde_json::Value::as_str),
-        ) {
-            (Some(token), Some(sig)) => (token, sig),
-            (None, ..) => return Err(Error::FindToken(channel.to_string())),
-            (.., None) => return Err(Error::FindSignature(channel.to_string())),
-        };
-
-        attohttpc::get(format!(
-            "https://usher.ttvnw.net/api/channel/hls/{}.m3u8",
-            channel,
-        ))
-        .params(&[
-            ("token", token),
-            ("sig", sig),
-            ("player_backend", "html5"),
-            ("player", "twitchweb"),
-            ("type", "any"),
-            ("allow_source", "true"),
-        ])
-        .send()
-        .map_err(|err| Error::GetPlaylist(channel.to_string(), err))?
-        .text()
-        .map_err(|err| Error::GetResponseBody(channel.to_string(), err))
-    }
-}
-
-#[derive(Debug, Clone, Serialize, PartialEq, PartialOrd, Eq, Ord)]
-struct Stream {
-    resolution: String,
-    bandwidth: String,
-    link: String,
-    #[serde(skip)]
-    quality: Option<u32>,
-    #[serde(rename = "type")]
-    ty: String,
-}
+mod debug;
+#[cfg(unix)]
+mod mpv;
+mod record;
 
 #[derive(Debug, Clone, PartialEq)]
 enum Quality {
@@ -223,13 +46,150 @@ impl From<Stream> for Item {
 
 impl std::fmt::Display for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "[{}] {: >10} @ {: >8.2} kbps",
-            self.quality,
-            self.resolution,
-            self.bitrate.parse::<f64>().unwrap() / 1024.
-        )
+        // BANDWIDTH is optional, so the bitrate can be missing
+        match self.bitrate.parse::<f64>() {
+            Ok(bitrate) => write!(
+                f,
+                "[{}] {: >10} @ {: >8.2} kbps",
+                self.quality,
+                self.resolution,
+                bitrate / 1024.
+            ),
+            Err(..) => write!(
+                f,
+                "[{}] {: >10} @ {: >8} kbps",
+                self.quality, self.resolution, "?"
+            ),
+        }
+    }
+}
+
+struct Resolved {
+    streams: Vec<Stream>,
+    audio: Vec<AudioTrack>,
+    stream: Stream,
+    track: Option<AudioTrack>,
+}
+
+fn resolve(
+    client: &Client,
+    channel: &str,
+    quality: &Quality,
+    audio_track: Option<&str>,
+) -> Result<Resolved, String> {
+    let Playlist { streams, audio } = client.get(channel).map_err(|err| err.to_string())?;
+    let stream = find_stream(channel, &streams, quality)?.clone();
+    let track = audio_track
+        .map(|query| find_audio(channel, &audio, &stream, query).cloned())
+        .transpose()?;
+
+    Ok(Resolved {
+        streams,
+        audio,
+        stream,
+        track,
+    })
+}
+
+// a stream or an item, with the audio tracks that go with it
+#[derive(Serialize)]
+struct WithAudio<'a, T> {
+    #[serde(flatten)]
+    inner: T,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    audio: Vec<&'a AudioTrack>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track: Option<&'a AudioTrack>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Output<'a> {
+    Streams(Vec<WithAudio<'a, &'a Stream>>),
+    Stream(WithAudio<'a, &'a Stream>),
+    Items(Vec<WithAudio<'a, Item>>),
+    Item(WithAudio<'a, Item>),
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    streams: Option<Output<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Resolved {
+    fn output(&self, list: bool, singular: bool) -> Output<'_> {
+        match (list, singular) {
+            (false, false) => {
+                Output::Streams(self.streams.iter().map(|s| self.with_audio(s, s)).collect())
+            }
+            (false, true) => Output::Stream(self.with_audio(&self.stream, &self.stream)),
+            (true, false) => Output::Items(
+                self.streams
+                    .iter()
+                    .map(|s| self.with_audio(s, Item::from(s.clone())))
+                    .collect(),
+            ),
+            (true, true) => {
+                Output::Item(self.with_audio(&self.stream, Item::from(self.stream.clone())))
+            }
+        }
+    }
+
+    // the tracks in the stream's audio group, and the selected one if it's the selected stream
+    fn with_audio<'a, T>(&'a self, stream: &Stream, inner: T) -> WithAudio<'a, T> {
+        let audio = self
+            .audio
+            .iter()
+            .filter(|track| {
+                stream
+                    .audio
+                    .as_ref()
+                    .is_none_or(|group| *group == track.group)
+            })
+            .collect();
+        let track = self.track.as_ref().filter(|_| *stream == self.stream);
+        WithAudio {
+            inner,
+            audio,
+            track,
+        }
+    }
+
+    // like `output`, but always a list
+    fn output_list(&self, list: bool, singular: bool) -> Output<'_> {
+        match self.output(list, singular) {
+            Output::Stream(stream) => Output::Streams(vec![stream]),
+            Output::Item(item) => Output::Items(vec![item]),
+            output => output,
+        }
+    }
+
+    fn lines(&self, singular: bool) -> Vec<String> {
+        let mut out = vec![];
+        if !self.audio.is_empty() {
+            out.push("audio tracks:".to_string());
+            match &self.track {
+                Some(track) => out.push(track.to_string()),
+                None => out.extend(self.audio.iter().map(ToString::to_string)),
+            }
+            out.push(String::new());
+        }
+
+        if singular {
+            out.push(Item::from(self.stream.clone()).to_string());
+        } else {
+            out.extend(
+                self.streams
+                    .iter()
+                    .cloned()
+                    .map(|s| Item::from(s).to_string()),
+            );
+        }
+        out
     }
 }
 
@@ -240,7 +200,7 @@ trait Abort<T, E = ()> {
 impl<T, E: std::fmt::Display> Abort<T, E> for Result<T, E> {
     fn abort<F: FnOnce(E) -> String>(self, f: F) -> T {
         self.unwrap_or_else(|err| {
-            eprintln!("{}", f(err));
+            eprintln!("{}", redact::redact(&f(err)));
             std::process::exit(1);
         })
     }
@@ -248,7 +208,7 @@ impl<T, E: std::fmt::Display> Abort<T, E> for Result<T, E> {
 impl<T> Abort<T, ()> for Option<T> {
     fn abort<F: FnOnce(()) -> String>(self, f: F) -> T {
         self.unwrap_or_else(|| {
-            eprintln!("{}", f(()));
+            eprintln!("{}", redact::redact(&f(())));
             std::process::exit(1);
         })
     }
@@ -271,101 +231,335 @@ struct Args {
     #[options(help = "list stream quality information")]
     list: bool,
 
-    #[options(required, free, help = "the stream to fetch")]
-    stream: String,
+    #[options(
+        meta = "TRACK",
+        help = "alternate audio track to use, by name, language or group"
+    )]
+    audio_track: Option<String>,
+
+    #[options(help = "log the requests being made to stderr")]
+    verbose: bool,
+
+    #[options(no_short, help = "don't mask tokens and signatures in logs and errors")]
+    log_unredacted: bool,
+
+    #[options(
+        no_short,
+        meta = "URL",
+        help = "base url for the twitch api (or `TWITCHLINK_API_URL`)"
+    )]
+    api_url: Option<String>,
+
+    #[options(
+        no_short,
+        meta = "URL",
+        help = "base url for the usher playlist service (or `TWITCHLINK_USHER_URL`)"
+    )]
+    usher_url: Option<String>,
+
+    #[options(
+        meta = "TARGET",
+        help = "record to a file, `-` for stdout, `udp://host:port` or `srt://host:port`"
+    )]
+    output: Option<String>,
+
+    #[options(help = "play in mpv and switch between the streams from the terminal")]
+    ipc: bool,
+
+    #[options(
+        no_short,
+        help = "with several streams, report the ones that fail and keep going"
+    )]
+    keep_going: bool,
+
+    #[options(required, free, help = "the streams to fetch")]
+    streams: Vec<String>,
 }
 
-fn main() {
-    let player = std::env::var("STREAMLINK_PLAYER")
-        .ok()
-        .unwrap_or_else(|| "mpv".to_string());
-
-    // TODO show the version
-    let args = Args::parse_args_default_or_exit();
-
-    let player = args.player.unwrap_or_else(|| player.to_string());
-    if std::fs::metadata(&player).is_err() {
-        eprintln!("error: invalid path: {}. set `STREAMLINK_PLAYER` or provide a path to a valid executable", player);
-        std::process::exit(1);
+// arguments for the player to use this track, if it knows how
+fn player_args(track: &AudioTrack, player: &str) -> Option<Vec<String>> {
+    let name = player_name(player);
+    match (name.as_str(), &track.link, &track.language) {
+        ("mpv", Some(link), ..) => Some(vec![format!("--audio-file={}", link)]),
+        ("mpv", None, Some(lang)) => Some(vec![format!("--alang={}", lang)]),
+        ("vlc", Some(link), ..) => Some(vec![format!("--input-slave={}", link)]),
+        ("vlc", None, Some(lang)) => Some(vec![format!("--audio-language={}", lang)]),
+        _ => None,
     }
+}
 
-    let channel = if args.stream.contains('/') {
-        args.stream.split('/').last().unwrap()
-    } else {
-        args.stream.as_str()
-    };
-
-    let id = std::env::var("TWITCH_CLIENT_ID")
-        .abort(|_| "env. var 'TWITCH_CLIENT_ID' must be set to your client id".to_string());
+fn player_name(player: &str) -> String {
+    std::path::Path::new(player)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
 
-    let client = Client::new(id);
-    let streams = client.get(&channel).abort(|err| err.to_string());
+fn channel_name(stream: &str) -> &str {
+    if stream.contains('/') {
+        stream.split('/').next_back().unwrap()
+    } else {
+        stream
+    }
+}
 
-    let singular = args.quality.is_some();
+fn find_stream<'a>(
+    channel: &str,
+    streams: &'a [Stream],
+    quality: &Quality,
+) -> Result<&'a Stream, String> {
+    if streams.is_empty() {
+        return Err(format!("stream `{}` is offline", channel));
+    }
 
-    let quality = args.quality.unwrap_or_else(|| Quality::Best);
-    let stream = match quality {
+    match quality {
         Quality::Best => streams
             .first()
-            .abort(|_| format!("stream `{}` is offline", channel)),
+            .ok_or_else(|| format!("stream `{}` is offline", channel)),
 
         Quality::Lowest => streams
             .last()
-            .abort(|_| format!("stream `{}` is offline", channel)),
+            .ok_or_else(|| format!("stream `{}` is offline", channel)),
 
-        Quality::Custom(mut s) => {
+        Quality::Custom(s) => {
+            let mut s = s.clone();
             if !s.ends_with('p') {
                 s.push('p');
             }
             streams
                 .iter()
                 .find(|stream| stream.ty == *s)
-                .abort(|_| format!("quality `{}` is not available for stream `{}` ", s, channel))
+                .ok_or_else(|| {
+                    format!("quality `{}` is not available for stream `{}` ", s, channel)
+                })
         }
-    };
+    }
+}
+
+// prefers the tracks in the audio group of the stream
+fn find_audio<'a>(
+    channel: &str,
+    audio: &'a [AudioTrack],
+    stream: &Stream,
+    query: &str,
+) -> Result<&'a AudioTrack, String> {
+    audio
+        .iter()
+        .filter(|track| stream.audio.as_ref() == Some(&track.group))
+        .chain(audio.iter())
+        .find(|track| track.matches(query))
+        .ok_or_else(|| {
+            format!(
+                "audio track `{}` is not available for stream `{}`",
+                query, channel
+            )
+        })
+}
+
+fn make_client(api_url: Option<String>, usher_url: Option<String>) -> Client {
+    let id = std::env::var("TWITCH_CLIENT_ID")
+        .abort(|_| "env. var 'TWITCH_CLIENT_ID' must be set to your client id".to_string());
+    redact::add_secret("client-id", &id);
+
+    let mut client = Client::new(id);
+    if let Some(url) = api_url.or_else(|| std::env::var("TWITCHLINK_API_URL").ok()) {
+        client = client.with_api_url(url);
+    }
+    if let Some(url) = usher_url.or_else(|| std::env::var("TWITCHLINK_USHER_URL").ok()) {
+        client = client.with_usher_url(url);
+    }
+    client
+}
+
+fn main() {
+    report::install();
+
+    let argv = std::env::args().skip(1).collect::<Vec<_>>();
+    if argv.first().map(String::as_str) == Some("debug")
+        && argv.get(1).is_some_and(|cmd| debug::is_command(cmd))
+    {
+        debug::run(&argv[1..]);
+        return;
+    }
+
+    let player = std::env::var("STREAMLINK_PLAYER")
+        .ok()
+        .unwrap_or_else(|| "mpv".to_string());
 
-    if args.json && !args.list {
-        let s = if !singular {
-            serde_json::to_string(&streams)
+    // TODO show the version
+    let args = Args::parse_args_default_or_exit();
+    redact::set_unredacted(args.log_unredacted);
+    VERBOSE.store(args.verbose, std::sync::atomic::Ordering::SeqCst);
+
+    let player = args.player.clone().unwrap_or(player);
+    let playing = !args.json && !args.list && args.output.is_none();
+    if playing && std::fs::metadata(&player).is_err() {
+        eprintln!("error: invalid path: {}. set `STREAMLINK_PLAYER` or provide a path to a valid executable", player);
+        std::process::exit(1);
+    }
+    if args.ipc && !playing {
+        eprintln!("error: `--ipc` plays the stream in mpv, so it can't be used with `--output`, `--json` or `--list`");
+        std::process::exit(1);
+    }
+    if args.ipc && !cfg!(unix) {
+        eprintln!("error: `--ipc` is only supported on unix for now");
+        std::process::exit(1);
+    }
+
+    let client = make_client(args.api_url.clone(), args.usher_url.clone());
+    let channels = args
+        .streams
+        .iter()
+        .map(|s| channel_name(s))
+        .collect::<Vec<_>>();
+
+    let singular = args.quality.is_some();
+    let quality = args.quality.clone().unwrap_or(Quality::Best);
+    let audio_track = args.audio_track.as_deref();
+
+    #[cfg(unix)]
+    if playing && args.ipc {
+        mpv::run(&player, &client, &channels, &quality, audio_track);
+        return;
+    }
+
+    if let [channel] = channels.as_slice() {
+        let resolved = resolve(&client, channel, &quality, audio_track).abort(|err| err);
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string(&resolved.output(args.list, singular)).unwrap()
+            );
+        } else if args.list {
+            resolved
+                .lines(singular)
+                .iter()
+                .for_each(|line| println!("{}", line));
+        } else if let Some(target) = &args.output {
+            record(&client, channel, &resolved, target);
         } else {
-            serde_json::to_string(&stream)
+            play(&player, channel, &resolved);
         }
-        .unwrap();
-
-        println!("{}", s);
         return;
     }
 
-    match (args.json, args.list, singular) {
-        (false, true, false) => streams
+    if !args.json && !args.list {
+        eprintln!("only one stream can be played or recorded at a time. use `--list` or `--json` with several streams");
+        std::process::exit(1);
+    }
+
+    let results = std::thread::scope(|scope| {
+        let handles = channels
+            .iter()
+            .map(|&channel| {
+                let (client, quality) = (&client, &quality);
+                scope.spawn(move || resolve(client, channel, quality, audio_track))
+            })
+            .collect::<Vec<_>>();
+        handles
             .into_iter()
-            .map(Item::from)
-            .for_each(|k| println!("{}", k)),
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    if !args.keep_going {
+        if let Some(Err(err)) = results.iter().find(|res| res.is_err()) {
+            Err::<(), _>(err).abort(|err| err.to_string());
+        }
+    }
 
-        (false, true, true) => println!("{}", Item::from(stream.clone())),
+    let failed = results.iter().filter(|res| res.is_err()).count();
+    if args.json {
+        let entries = channels
+            .iter()
+            .zip(results.iter())
+            .map(|(channel, res)| Entry {
+                channel,
+                streams: res
+                    .as_ref()
+                    .ok()
+                    .map(|r| r.output_list(args.list, singular)),
+                error: res
+                    .as_ref()
+                    .err()
+                    .map(|err| redact::redact(err).into_owned()),
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string(&entries).unwrap());
+    } else {
+        for (channel, res) in channels.iter().zip(results.iter()) {
+            match res {
+                Ok(resolved) => {
+                    println!("{}:", channel);
+                    resolved
+                        .lines(singular)
+                        .iter()
+                        .for_each(|line| match line.as_str() {
+                            "" => println!(),
+                            line => println!("  {}", line),
+                        });
+                }
+                Err(err) => println!("{}: error: {}", channel, redact::redact(err)),
+            }
+        }
+    }
 
-        (true, true, true) => println!(
-            "{}",
-            serde_json::to_string(&Item::from(stream.clone())).unwrap()
-        ),
+    if failed > 0 {
+        eprintln!("{} of {} streams failed", failed, channels.len());
+        std::process::exit(1);
+    }
+}
 
-        (true, true, false) => println!(
-            "{}",
-            serde_json::to_string(
-                &streams.into_iter().map(Item::from).collect::<Vec<_>>() //
-            )
-            .unwrap()
+fn record(client: &Client, channel: &str, resolved: &Resolved, target: &str) {
+    if resolved.track.is_some() {
+        eprintln!("WARN: alternate audio tracks aren't recorded, ignoring it");
+    }
+
+    let mut output = record::Output::open(target).abort(|err| match err.kind() {
+        std::io::ErrorKind::NotFound if target.starts_with("srt://") => format!(
+            "cannot open output `{}`. srt needs `ffmpeg` (or set `TWITCHLINK_FFMPEG`)\nerror: {}",
+            target, err
         ),
+        _ => format!("cannot open output `{}` because: {}", target, err),
+    });
+
+    eprintln!(
+        "recording `{}` ({}) to {}",
+        channel, resolved.stream.ty, target
+    );
+    record::record(client, &resolved.stream, target, &mut output).abort(|err| err.to_string());
+    eprintln!("stream `{}` has ended", channel);
+}
 
-        _ => std::process::Command::new(&player)
-            .arg(&stream.link)
-            .spawn()
-            .map(|_| ())
-            .abort(|err| {
-                format!(
-                    "cannot start stream `{}`. make sure `{}` is a valid player\nerror: {}",
-                    channel, player, err
-                )
-            }),
-    }
+fn play(player: &str, channel: &str, resolved: &Resolved) {
+    let args = resolved
+        .track
+        .as_ref()
+        .map(|track| {
+            let args = player_args(track, player);
+            if args.is_none() {
+                eprintln!(
+                    "WARN: don't know how to select an audio track for `{}`, ignoring it",
+                    player
+                );
+            }
+            args.unwrap_or_default()
+        })
+        .unwrap_or_default();
+
+    std::process::Command::new(player)
+        .args(args)
+        .arg(&resolved.stream.link)
+        .spawn()
+        .map(|_| ())
+        .abort(|err| {
+            format!(
+                "cannot start stream `{}`. make sure `{}` is a valid player\nerror: {}",
+                channel, player, err
+            )
+        })
 }
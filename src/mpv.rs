@@ -0,0 +1,560 @@
+use std::ffi::OsString;
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::os::unix::fs::DirBuilderExt as _;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{channel_name, player_args, player_name, resolve, Abort, Quality, Resolved};
+use twitchlink::{redact, verbose, AudioTrack, Client};
+
+// only unix sockets for now, mpv's named pipes on windows need overlapped io
+type Conn = std::os::unix::net::UnixStream;
+
+// how long to wait for mpv to create its ipc socket
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// the keys bound in mpv to switch to the next and previous streams
+const NEXT_KEY: &str = "ctrl+n";
+const PREV_KEY: &str = "ctrl+p";
+
+enum Event {
+    Input(String),
+    Next,
+    Prev,
+    Failed(String),
+}
+
+struct Mpv {
+    conn: Conn,
+    child: Child,
+    // kept around so it's removed when mpv is done
+    _socket: Socket,
+}
+
+impl Mpv {
+    fn spawn(player: &str, resolved: &Resolved, channel: &str) -> std::io::Result<Self> {
+        let socket = Socket::new(std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from))?;
+        let mut ipc = OsString::from("--input-ipc-server=");
+        ipc.push(&socket.path);
+
+        let mut child = Command::new(player)
+            .arg(ipc)
+            .arg(format!("--force-media-title={}", channel))
+            // the terminal is used for switching streams
+            .arg("--no-terminal")
+            .args(
+                resolved
+                    .track
+                    .as_ref()
+                    .and_then(|track| player_args(track, player))
+                    .unwrap_or_default(),
+            )
+            .arg(&resolved.stream.link)
+            .stdin(Stdio::null())
+            .spawn()?;
+
+        let start = Instant::now();
+        let conn = loop {
+            match connect(&socket.path) {
+                Ok(conn) => break conn,
+                Err(..) if start.elapsed() < CONNECT_TIMEOUT => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(std::io::Error::other(format!("mpv exited with {}", status)));
+                    }
+                    std::thread::sleep(Duration::from_millis(100))
+                }
+                Err(err) => {
+                    let _ = child.kill();
+                    return Err(err);
+                }
+            }
+        };
+
+        Ok(Self {
+            conn,
+            child,
+            _socket: socket,
+        })
+    }
+
+    fn command(&mut self, args: serde_json::Value) -> std::io::Result<()> {
+        let line = serde_json::json!({ "command": args }).to_string();
+        verbose!("mpv <- {}", line);
+        writeln!(self.conn, "{}", line)?;
+        self.conn.flush()
+    }
+
+    fn load(&mut self, resolved: &Resolved, channel: &str) -> std::io::Result<()> {
+        // these only apply when the next file is loaded
+        match resolved.track.as_ref() {
+            Some(AudioTrack {
+                link: Some(link), ..
+            }) => self.command(serde_json::json!([
+                "change-list",
+                "audio-files",
+                "set",
+                link
+            ]))?,
+            Some(AudioTrack {
+                language: Some(lang),
+                ..
+            }) => {
+                self.command(serde_json::json!(["change-list", "audio-files", "clr", ""]))?;
+                self.command(serde_json::json!(["set_property", "alang", lang]))?
+            }
+            _ => self.command(serde_json::json!(["change-list", "audio-files", "clr", ""]))?,
+        }
+        self.command(serde_json::json!([
+            "set_property",
+            "force-media-title",
+            channel
+        ]))?;
+        self.command(serde_json::json!([
+            "loadfile",
+            resolved.stream.link,
+            "replace"
+        ]))
+    }
+
+    fn events(&self, tx: Sender<Event>) -> std::io::Result<()> {
+        let conn = BufReader::new(self.conn.try_clone()?);
+        std::thread::spawn(move || {
+            for line in conn.lines().map_while(Result::ok) {
+                verbose!("mpv -> {}", line);
+                let val = match serde_json::from_str::<serde_json::Value>(&line) {
+                    Ok(val) => val,
+                    Err(..) => continue,
+                };
+
+                let event = match val.get("event").and_then(serde_json::Value::as_str) {
+                    Some("client-message") => {
+                        match val.pointer("/args/0").and_then(serde_json::Value::as_str) {
+                            Some("twitchlink-next") => Event::Next,
+                            Some("twitchlink-prev") => Event::Prev,
+                            _ => continue,
+                        }
+                    }
+                    Some("end-file")
+                        if val.get("reason").and_then(|s| s.as_str()) == Some("error") =>
+                    {
+                        let err = val
+                            .get("file_error")
+                            .and_then(serde_json::Value::as_str)
+                            .unwrap_or("unknown error");
+                        Event::Failed(err.to_string())
+                    }
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+// where mpv listens. anyone who can get to the socket can tell mpv to run commands, so
+// it goes in the user's runtime dir, or else in a new directory only the user can open
+struct Socket {
+    path: PathBuf,
+    // the directory made for the socket, if there's no runtime dir
+    dir: Option<PathBuf>,
+}
+
+impl Socket {
+    fn new(runtime_dir: Option<PathBuf>) -> std::io::Result<Self> {
+        let name = format!("twitchlink-mpv-{}.sock", std::process::id());
+        if let Some(dir) = runtime_dir.filter(|dir| dir.is_dir()) {
+            return Ok(Self {
+                path: dir.join(name),
+                dir: None,
+            });
+        }
+
+        let mut attempts = 0;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or_default();
+            let dir = std::env::temp_dir().join(format!(
+                "twitchlink-{}-{:08x}",
+                std::process::id(),
+                nanos
+            ));
+            // this fails if the directory exists, so it can't be one that somebody else made
+            match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+                Ok(()) => {
+                    return Ok(Self {
+                        path: dir.join(name),
+                        dir: Some(dir),
+                    })
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempts < 5 => {
+                    attempts += 1
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+fn connect(path: &Path) -> std::io::Result<Conn> {
+    Conn::connect(path)
+}
+
+struct Switcher<'a> {
+    client: &'a Client,
+    quality: &'a Quality,
+    audio_track: Option<&'a str>,
+    channels: Vec<String>,
+    // the channels that were given or typed in, these stay in the list
+    pinned: Vec<String>,
+    current: String,
+    previous: Option<String>,
+    // with an oauth token, the live channels that the user follows are listed too
+    oauth: Option<String>,
+    followed: Vec<String>,
+}
+
+impl Switcher<'_> {
+    fn menu(&self) {
+        eprintln!(
+            "type a number or a channel to switch streams, `-` for the previous one, `q` to quit"
+        );
+        eprintln!(
+            "in mpv, {} and {} switch to the next and previous stream",
+            NEXT_KEY, PREV_KEY
+        );
+        if self.oauth.is_some() {
+            eprintln!("`r` refreshes the live channels you follow");
+        }
+        for (i, channel) in self.channels.iter().enumerate() {
+            let followed = if self.followed.contains(channel) {
+                " (followed)"
+            } else {
+                ""
+            };
+            let playing = if *channel == self.current {
+                " (playing)"
+            } else {
+                ""
+            };
+            eprintln!("  {}) {}{}{}", i + 1, channel, followed, playing);
+        }
+    }
+
+    // updates the list with the followed channels that are live
+    fn refresh(&mut self) {
+        let oauth = match self.oauth.clone() {
+            Some(oauth) => oauth,
+            None => {
+                eprintln!("set `TWITCH_OAUTH_TOKEN` to list the channels you follow");
+                return;
+            }
+        };
+
+        match self.client.followed(&oauth) {
+            Ok(live) => self.set_followed(live.iter().map(|s| normalize(s)).collect()),
+            Err(err) => eprintln!(
+                "cannot get followed channels: {}",
+                redact::redact(&err.to_string())
+            ),
+        }
+    }
+
+    // followed channels that went offline are dropped, unless they're pinned or playing
+    fn set_followed(&mut self, live: Vec<String>) {
+        let (pinned, current) = (&self.pinned, &self.current);
+        self.channels.retain(|channel| {
+            pinned.contains(channel) || live.contains(channel) || channel == current
+        });
+        for channel in &live {
+            if !self.channels.contains(channel) {
+                self.channels.push(channel.clone())
+            }
+        }
+        self.followed = live;
+    }
+
+    // whether mpv is playing the channel now
+    fn switch(&mut self, mpv: &mut Mpv, channel: &str) -> bool {
+        if channel == self.current {
+            return true;
+        }
+
+        eprintln!("switching to `{}`", channel);
+        let resolved =
+            match resolve_with_fallback(self.client, channel, self.quality, self.audio_track) {
+                Ok(resolved) => resolved,
+                Err(err) => {
+                    eprintln!("cannot switch to `{}`: {}", channel, redact::redact(&err));
+                    return false;
+                }
+            };
+
+        if let Err(err) = mpv.load(&resolved, channel) {
+            eprintln!("cannot talk to mpv: {}", err);
+            return false;
+        }
+        let previous = std::mem::replace(&mut self.current, channel.to_string());
+        self.previous.replace(previous);
+        true
+    }
+
+    // the channel `offset` places from the current one in the list
+    fn nearby(&self, offset: isize) -> String {
+        let len = self.channels.len() as isize;
+        let pos = self
+            .channels
+            .iter()
+            .position(|channel| *channel == self.current)
+            .unwrap_or_default() as isize;
+        self.channels[(pos + offset).rem_euclid(len) as usize].clone()
+    }
+
+    // a typed channel is only added to the list once it's playing
+    fn pin(&mut self, channel: String) {
+        if !self.channels.contains(&channel) {
+            self.channels.push(channel.clone())
+        }
+        if !self.pinned.contains(&channel) {
+            self.pinned.push(channel)
+        }
+    }
+
+    fn input(&mut self, mpv: &mut Mpv, line: &str) -> bool {
+        match line {
+            "" => self.menu(),
+            "q" | "quit" => return false,
+            "r" => {
+                self.refresh();
+                self.menu()
+            }
+            "-" => match self.previous.clone() {
+                Some(previous) => {
+                    self.switch(mpv, &previous);
+                }
+                None => eprintln!("there is no previous stream"),
+            },
+            line => match line.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= self.channels.len() => {
+                    let channel = self.channels[n - 1].clone();
+                    self.switch(mpv, &channel);
+                }
+                Ok(..) => eprintln!("there is no stream #{}", line),
+                Err(..) => {
+                    let channel = normalize(line);
+                    if self.switch(mpv, &channel) {
+                        self.pin(channel)
+                    }
+                }
+            },
+        }
+        true
+    }
+}
+
+// channel names aren't case sensitive, so `Foo` and `foo` are the same entry
+fn normalize(stream: &str) -> String {
+    channel_name(stream).to_ascii_lowercase()
+}
+
+// not every channel has the same audio tracks, so fall back to the default one
+fn resolve_with_fallback(
+    client: &Client,
+    channel: &str,
+    quality: &Quality,
+    audio_track: Option<&str>,
+) -> Result<Resolved, String> {
+    match (resolve(client, channel, quality, audio_track), audio_track) {
+        (Err(err), Some(track)) => match resolve(client, channel, quality, None) {
+            Ok(resolved) => {
+                eprintln!(
+                    "audio track `{}` is not available for `{}`, using the default",
+                    track, channel
+                );
+                Ok(resolved)
+            }
+            Err(..) => Err(err),
+        },
+        (res, ..) => res,
+    }
+}
+
+// plays the first channel in mpv, and switches between them until mpv exits
+pub fn run(
+    player: &str,
+    client: &Client,
+    channels: &[&str],
+    quality: &Quality,
+    audio_track: Option<&str>,
+) {
+    if player_name(player) != "mpv" {
+        eprintln!("`--ipc` only works with mpv, not `{}`", player);
+        std::process::exit(1);
+    }
+
+    let mut list = Vec::<String>::new();
+    for channel in channels.iter().map(|s| normalize(s)) {
+        if !list.contains(&channel) {
+            list.push(channel)
+        }
+    }
+
+    let first = list[0].as_str();
+    let resolved = resolve_with_fallback(client, first, quality, audio_track).abort(|err| err);
+
+    let mut mpv = Mpv::spawn(player, &resolved, first).abort(|err| {
+        format!(
+            "cannot start stream `{}` with mpv ipc. make sure `{}` is mpv\nerror: {}",
+            first, player, err
+        )
+    });
+
+    let (tx, rx) = mpsc::channel();
+    mpv.events(tx.clone())
+        .abort(|err| format!("cannot read from mpv: {}", err));
+
+    for (key, message) in &[(NEXT_KEY, "twitchlink-next"), (PREV_KEY, "twitchlink-prev")] {
+        let _ = mpv.command(serde_json::json!([
+            "keybind",
+            key,
+            format!("script-message {}", message)
+        ]));
+    }
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if tx.send(Event::Input(line.trim().to_string())).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut switcher = Switcher {
+        client,
+        quality,
+        audio_track,
+        pinned: list.clone(),
+        current: list[0].clone(),
+        channels: list,
+        previous: None,
+        oauth: std::env::var("TWITCH_OAUTH_TOKEN").ok(),
+        followed: vec![],
+    };
+    if switcher.oauth.is_some() {
+        switcher.refresh();
+    }
+    switcher.menu();
+
+    loop {
+        match rx.recv_timeout(Duration::from_millis(250)) {
+            Ok(Event::Input(line)) => {
+                if !switcher.input(&mut mpv, &line) {
+                    let _ = mpv.command(serde_json::json!(["quit"]));
+                    break;
+                }
+            }
+            Ok(Event::Next) => {
+                let next = switcher.nearby(1);
+                switcher.switch(&mut mpv, &next);
+            }
+            Ok(Event::Prev) => {
+                let prev = switcher.nearby(-1);
+                switcher.switch(&mut mpv, &prev);
+            }
+            Ok(Event::Failed(err)) => eprintln!("mpv cannot play the stream: {}", err),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Ok(Some(..)) = mpv.child.try_wait() {
+            break;
+        }
+    }
+
+    // give mpv a moment to quit on its own
+    let start = Instant::now();
+    while let Ok(None) = mpv.child.try_wait() {
+        if start.elapsed() > Duration::from_secs(2) {
+            let _ = mpv.child.kill();
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn socket_in_a_private_dir() {
+        let socket = Socket::new(None).unwrap();
+        let dir = socket.dir.clone().unwrap();
+        assert_eq!(socket.path.parent(), Some(dir.as_path()));
+
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        drop(socket);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn socket_in_the_runtime_dir() {
+        let runtime = std::env::temp_dir();
+        let socket = Socket::new(Some(runtime.clone())).unwrap();
+        assert!(socket.dir.is_none());
+        assert_eq!(socket.path.parent(), Some(runtime.as_path()));
+    }
+
+    #[test]
+    fn offline_followed_channels_are_dropped() {
+        let client = Client::new("test-client-id");
+        let mut switcher = Switcher {
+            client: &client,
+            quality: &Quality::Best,
+            audio_track: None,
+            channels: names(&["given"]),
+            pinned: names(&["given"]),
+            current: "given".to_string(),
+            previous: None,
+            oauth: None,
+            followed: vec![],
+        };
+
+        switcher.set_followed(names(&["a", "b", "c"]));
+        assert_eq!(switcher.channels, ["given", "a", "b", "c"]);
+
+        // `b` is playing and `c` was typed in, so they stay
+        switcher.current = "b".to_string();
+        switcher.pin("c".to_string());
+        switcher.set_followed(names(&["d"]));
+        assert_eq!(switcher.channels, ["given", "b", "c", "d"]);
+        assert_eq!(switcher.followed, ["d"]);
+
+        assert_eq!(switcher.nearby(1), "c");
+        assert_eq!(switcher.nearby(-1), "given");
+        switcher.current = "d".to_string();
+        assert_eq!(switcher.nearby(1), "given");
+    }
+}
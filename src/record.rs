@@ -0,0 +1,211 @@
+use std::io::Write as _;
+use std::net::{SocketAddr, ToSocketAddrs as _, UdpSocket};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use twitchlink::hls::Media;
+use twitchlink::{redact, Client, Error, Stream};
+
+// 7 mpeg-ts packets, the usual payload for ts over udp
+const UDP_PAYLOAD: usize = 7 * 188;
+
+// how many segments behind the live edge to start at
+const LIVE_EDGE: usize = 3;
+// how many times in a row the media playlist can fail before giving up
+const MAX_FAILURES: u32 = 10;
+
+pub enum Output {
+    File(std::fs::File),
+    Stdout(std::io::Stdout),
+    Udp(UdpSocket, SocketAddr),
+    // srt is handed off to ffmpeg
+    Process(Child),
+}
+
+impl Output {
+    pub fn open(target: &str) -> std::io::Result<Self> {
+        if target == "-" {
+            return Ok(Output::Stdout(std::io::stdout()));
+        }
+        if let Some(addr) = target.strip_prefix("udp://") {
+            return udp(addr);
+        }
+        if target.starts_with("srt://") {
+            return srt(target);
+        }
+        std::fs::File::create(target).map(Output::File)
+    }
+
+    // `duration` is how long the data plays for, udp sends are spread out over it
+    fn write(&mut self, data: &[u8], duration: f64) -> std::io::Result<()> {
+        match self {
+            Output::File(file) => file.write_all(data),
+            Output::Stdout(stdout) => {
+                let mut stdout = stdout.lock();
+                stdout.write_all(data)?;
+                stdout.flush()
+            }
+            Output::Udp(socket, addr) => {
+                let chunks = data.len().div_ceil(UDP_PAYLOAD).max(1);
+                // a bit faster than real time, so it doesn't fall behind the live edge
+                let pace = Duration::from_secs_f64(duration.max(0.0) * 0.9 / chunks as f64);
+                for chunk in data.chunks(UDP_PAYLOAD) {
+                    socket.send_to(chunk, *addr)?;
+                    std::thread::sleep(pace);
+                }
+                Ok(())
+            }
+            Output::Process(child) => child
+                .stdin
+                .as_mut()
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "ffmpeg isn't running")
+                })?
+                .write_all(data),
+        }
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Output::Process(child) = self {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
+
+// host:port, optionally with ?ttl=N
+fn udp(addr: &str) -> std::io::Result<Output> {
+    let (addr, query) = match addr.split_once('?') {
+        Some((addr, query)) => (addr, Some(query)),
+        None => (addr, None),
+    };
+    let ttl = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("ttl="))
+        .and_then(|ttl| ttl.parse::<u32>().ok());
+
+    let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "cannot resolve address")
+    })?;
+
+    let socket = if addr.is_ipv4() {
+        UdpSocket::bind("0.0.0.0:0")?
+    } else {
+        UdpSocket::bind("[::]:0")?
+    };
+
+    if let Some(ttl) = ttl {
+        if addr.ip().is_multicast() && addr.is_ipv4() {
+            socket.set_multicast_ttl_v4(ttl)?;
+        } else {
+            socket.set_ttl(ttl)?;
+        }
+    }
+    // not connected, so nobody listening yet (ECONNREFUSED) doesn't stop the recording
+    Ok(Output::Udp(socket, addr))
+}
+
+fn srt(target: &str) -> std::io::Result<Output> {
+    let ffmpeg = std::env::var("TWITCHLINK_FFMPEG").unwrap_or_else(|_| "ffmpeg".to_string());
+    Command::new(ffmpeg)
+        .args(["-hide_banner", "-loglevel", "warning"])
+        .args(["-i", "pipe:0", "-c", "copy", "-f", "mpegts", target])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map(Output::Process)
+}
+
+pub fn record(
+    client: &Client,
+    stream: &Stream,
+    target: &str,
+    output: &mut Output,
+) -> Result<(), Error> {
+    let mut last: Option<u64> = None;
+    let mut failures = 0;
+    loop {
+        let start = Instant::now();
+        let media = match client.fetch_media(&stream.link) {
+            Ok(media) => {
+                failures = 0;
+                Media::parse(&media)
+            }
+            Err(err) if failures < MAX_FAILURES => {
+                failures += 1;
+                eprintln!(
+                    "WARN: {} (retrying, {}/{})",
+                    redact::redact(&err.to_string()),
+                    failures,
+                    MAX_FAILURES
+                );
+                std::thread::sleep(Duration::from_secs(failures.min(5).into()));
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        if let Some(seq) = last.filter(|&last| media.is_reset(last)) {
+            eprintln!(
+                "WARN: the playlist went back from sequence {}, starting over",
+                seq
+            );
+            last.take();
+        }
+
+        let skip = match last {
+            Some(last) => media
+                .segments
+                .iter()
+                .take_while(|seg| seg.sequence <= last)
+                .count(),
+            None => media.segments.len().saturating_sub(LIVE_EDGE),
+        };
+        for seg in media.segments.iter().skip(skip) {
+            last.replace(seg.sequence);
+            // a missing segment is a gap in the recording, but it can keep going
+            let data = match client.fetch_segment(&resolve_uri(&stream.link, &seg.uri)) {
+                Ok(data) => data,
+                Err(err) => {
+                    eprintln!(
+                        "WARN: skipping segment {}: {}",
+                        seg.sequence,
+                        redact::redact(&err.to_string())
+                    );
+                    continue;
+                }
+            };
+            output
+                .write(&data, seg.duration)
+                .map_err(|err| Error::Output(target.to_string(), err))?;
+        }
+
+        if media.ended {
+            return Ok(());
+        }
+
+        // poll at about half of the target duration
+        let wait = Duration::from_secs_f64(media.target_duration.unwrap_or(2.0) / 2.0);
+        if let Some(wait) = wait.checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+fn resolve_uri(base: &str, uri: &str) -> String {
+    if uri.contains("://") {
+        return uri.to_string();
+    }
+    let base = base.split('?').next().unwrap_or(base);
+    if uri.starts_with('/') {
+        let host = base.find("://").map_or(0, |pos| pos + 3);
+        let end = base[host..].find('/').map_or(base.len(), |pos| pos + host);
+        return format!("{}{}", &base[..end], uri);
+    }
+    match base.rfind('/') {
+        Some(pos) => format!("{}/{}", &base[..pos], uri.trim_start_matches('/')),
+        None => uri.to_string(),
+    }
+}
@@ -0,0 +1,280 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const MASK: &str = "<redacted>";
+
+// query parameters and json fields whose values are secret
+const KEYS: &[&str] = &["token", "sig", "signature", "access_token", "oauth_token"];
+// how many secrets are remembered, the oldest one is forgotten first
+const MAX_SECRETS: usize = 64;
+
+static UNREDACTED: AtomicBool = AtomicBool::new(false);
+// the key says what the secret is for (e.g. `token:<channel>`), so a new one replaces it
+static SECRETS: Mutex<VecDeque<(String, String)>> = Mutex::new(VecDeque::new());
+
+pub fn set_unredacted(unredacted: bool) {
+    UNREDACTED.store(unredacted, Ordering::SeqCst)
+}
+
+pub fn is_unredacted() -> bool {
+    UNREDACTED.load(Ordering::SeqCst)
+}
+
+pub fn add_secret(key: impl ToString, secret: impl ToString) {
+    let (key, secret) = (key.to_string(), secret.to_string());
+    if secret.is_empty() {
+        return;
+    }
+    let mut secrets = match SECRETS.lock() {
+        Ok(secrets) => secrets,
+        Err(err) => err.into_inner(),
+    };
+    secrets.retain(|(k, _)| *k != key);
+    if secrets.len() == MAX_SECRETS {
+        secrets.pop_front();
+    }
+    secrets.push_back((key, secret))
+}
+
+pub fn redact(input: &str) -> Cow<'_, str> {
+    if is_unredacted() {
+        return Cow::Borrowed(input);
+    }
+    mask(input)
+}
+
+// like `redact`, but ignores `--log-unredacted`
+pub fn mask(input: &str) -> Cow<'_, str> {
+    let mut out = Cow::Borrowed(input);
+
+    // known secrets first, they can contain characters that end a parameter
+    let secrets = match SECRETS.lock() {
+        Ok(secrets) => secrets,
+        Err(err) => err.into_inner(),
+    };
+    for (_, secret) in secrets.iter() {
+        if out.contains(secret.as_str()) {
+            out = Cow::Owned(out.replace(secret.as_str(), MASK))
+        }
+    }
+    drop(secrets);
+
+    for key in KEYS {
+        if let Some(s) = mask_params(&out, key) {
+            out = Cow::Owned(s)
+        }
+        if let Some(s) = mask_fields(&out, key) {
+            out = Cow::Owned(s)
+        }
+    }
+    for scheme in &["OAuth ", "Bearer "] {
+        if let Some(s) = mask_auth(&out, scheme) {
+            out = Cow::Owned(s)
+        }
+    }
+    out
+}
+
+// ?key=value or &key=value, up to the next separator
+fn mask_params(input: &str, key: &str) -> Option<String> {
+    let needle = format!("{}=", key);
+    let mut out = String::new();
+    let mut rest = input;
+    let mut found = false;
+
+    while let Some(pos) = rest.find(&needle) {
+        let start = pos + needle.len();
+        if pos == 0 || !matches!(rest.as_bytes()[pos - 1], b'?' | b'&' | b';') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            continue;
+        }
+
+        let end = rest[start..]
+            .find(|c: char| c == '&' || c == '"' || c == '\'' || c.is_whitespace())
+            .map(|end| end + start)
+            .unwrap_or_else(|| rest.len());
+
+        out.push_str(&rest[..start]);
+        out.push_str(MASK);
+        rest = &rest[end..];
+        found = true;
+    }
+
+    if !found {
+        return None;
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+// "key":"value", the value can contain escaped quotes
+fn mask_fields(input: &str, key: &str) -> Option<String> {
+    let needle = format!("\"{}\":", key);
+    let mut out = String::new();
+    let mut rest = input;
+    let mut found = false;
+
+    while let Some(pos) = rest.find(&needle) {
+        let head = pos + needle.len();
+        let start = match rest[head..].find(|c: char| !c.is_whitespace()) {
+            Some(n) if rest[head + n..].starts_with('"') => head + n + 1,
+            _ => {
+                out.push_str(&rest[..head]);
+                rest = &rest[head..];
+                continue;
+            }
+        };
+
+        let mut escaped = false;
+        let end = rest[start..]
+            .char_indices()
+            .find(|&(_, c)| {
+                let done = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                done
+            })
+            .map(|(end, _)| end + start)
+            .unwrap_or_else(|| rest.len());
+
+        out.push_str(&rest[..start]);
+        out.push_str(MASK);
+        rest = &rest[end..];
+        found = true;
+    }
+
+    if !found {
+        return None;
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+// Authorization: OAuth <token>, or Bearer <token>
+fn mask_auth(input: &str, scheme: &str) -> Option<String> {
+    if !input.contains(scheme) {
+        return None;
+    }
+
+    let mut out = String::new();
+    let mut rest = input;
+    while let Some(pos) = rest.find(scheme) {
+        let start = pos + scheme.len();
+        let end = rest[start..]
+            .find(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+            .map(|end| end + start)
+            .unwrap_or_else(|| rest.len());
+
+        out.push_str(&rest[..start]);
+        out.push_str(MASK);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params() {
+        let url = "http://usher/hls/foo.m3u8?player=twitchweb&token=abc123&sig=def456&type=any";
+        assert_eq!(
+            mask(url),
+            "http://usher/hls/foo.m3u8?player=twitchweb&token=<redacted>&sig=<redacted>&type=any"
+        );
+
+        assert_eq!(mask("http://api/?sig=def456"), "http://api/?sig=<redacted>");
+        assert_eq!(
+            mask("error for \"http://api/?token=abc\": 403"),
+            "error for \"http://api/?token=<redacted>\": 403"
+        );
+    }
+
+    #[test]
+    fn params_need_a_separator() {
+        let url = "http://api/?xtoken=abc&notsig=def";
+        assert_eq!(mask(url), url);
+    }
+
+    #[test]
+    fn fields() {
+        let json = r#"{"token":"{\"channel\":\"foo\",\"expires\":1}","sig":"def456","mobile_restricted":false}"#;
+        assert_eq!(
+            mask(json),
+            r#"{"token":"<redacted>","sig":"<redacted>","mobile_restricted":false}"#
+        );
+
+        assert_eq!(
+            mask(r#"{"access_token": "abc"}"#),
+            r#"{"access_token": "<redacted>"}"#
+        );
+        // only string values are masked
+        assert_eq!(mask(r#"{"sig":null}"#), r#"{"sig":null}"#);
+    }
+
+    #[test]
+    fn oauth() {
+        assert_eq!(
+            mask("Authorization: OAuth abc123\nAccept: */*"),
+            "Authorization: OAuth <redacted>\nAccept: */*"
+        );
+        assert_eq!(
+            mask("Authorization: Bearer abc123"),
+            "Authorization: Bearer <redacted>"
+        );
+    }
+
+    // the secrets are global, so the tests that fill them up can't run alongside each other
+    static SECRETS_TEST: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn secrets() {
+        let _guard = SECRETS_TEST.lock();
+        add_secret("redact-test", "redact-test-client-id");
+        add_secret("redact-test-empty", "");
+        assert_eq!(
+            mask("Client-ID: redact-test-client-id"),
+            "Client-ID: <redacted>"
+        );
+
+        // a new secret for the same key replaces the old one
+        add_secret("redact-test", "redact-test-new-id");
+        assert_eq!(
+            mask("redact-test-client-id redact-test-new-id"),
+            "redact-test-client-id <redacted>"
+        );
+    }
+
+    #[test]
+    fn secrets_are_bounded() {
+        let _guard = SECRETS_TEST.lock();
+        for n in 0..MAX_SECRETS * 2 {
+            add_secret(
+                format!("redact-bounded-{}", n),
+                format!("redact-bounded-value-{}", n),
+            );
+        }
+        let secrets = match SECRETS.lock() {
+            Ok(secrets) => secrets,
+            Err(err) => err.into_inner(),
+        };
+        assert_eq!(secrets.len(), MAX_SECRETS);
+        drop(secrets);
+
+        let last = format!("redact-bounded-value-{}", MAX_SECRETS * 2 - 1);
+        assert_eq!(mask(&last), MASK);
+        assert_eq!(mask("redact-bounded-value-0"), "redact-bounded-value-0");
+    }
+
+    #[test]
+    fn nothing_to_mask() {
+        assert!(matches!(
+            mask("http://api/channels/foo/access_token"),
+            Cow::Borrowed(..)
+        ));
+    }
+}
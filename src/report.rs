@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::io::Write as _;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::redact;
+
+const MAX_REQUESTS: usize = 16;
+
+static REQUESTS: Mutex<VecDeque<(String, u16)>> = Mutex::new(VecDeque::new());
+
+pub fn record(url: &str, status: u16) {
+    let url = url.split('?').next().unwrap_or(url).to_string();
+    let mut requests = requests();
+    if requests.len() == MAX_REQUESTS {
+        requests.pop_front();
+    }
+    requests.push_back((url, status));
+}
+
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        let args = std::env::args().collect::<Vec<_>>();
+        let report = build(
+            &info.to_string(),
+            &args,
+            &requests(),
+            &std::backtrace::Backtrace::force_capture(),
+        );
+        let path = std::env::temp_dir().join(format!(
+            "twitchlink-{}-{}.txt",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            std::process::id()
+        ));
+
+        eprintln!("twitchlink crashed: {}", redact::redact(&info.to_string()));
+        // don't follow a file (or symlink) someone else put there
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path);
+        match file.and_then(|mut fi| fi.write_all(report.as_bytes())) {
+            Ok(..) => eprintln!(
+                "a bug report was saved to: {}\nplease attach it when opening an issue",
+                path.display()
+            ),
+            Err(err) => eprintln!("cannot write bug report, because: {}\n{}", err, report),
+        }
+    }));
+}
+
+fn requests() -> MutexGuard<'static, VecDeque<(String, u16)>> {
+    match REQUESTS.lock() {
+        Ok(requests) => requests,
+        Err(err) => err.into_inner(),
+    }
+}
+
+// the report is meant to be shared, so it is always redacted
+fn build(
+    message: &str,
+    args: &[String],
+    requests: &VecDeque<(String, u16)>,
+    backtrace: &dyn std::fmt::Display,
+) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "twitchlink v{}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        out,
+        "os: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    let _ = writeln!(out, "args: {:?}", args);
+    let _ = writeln!(out, "\npanic: {}", message);
+
+    let _ = writeln!(out, "\nlast requests:");
+    if requests.is_empty() {
+        let _ = writeln!(out, "  (none)");
+    }
+    for (url, status) in requests.iter() {
+        let _ = writeln!(out, "  {} {}", status, url);
+    }
+
+    let _ = writeln!(out, "\nbacktrace:\n{}", backtrace);
+    redact::mask(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let report = build("oops", &[], &VecDeque::new(), &"");
+        let mut lines = report.lines();
+        assert_eq!(
+            lines.next(),
+            Some(format!("twitchlink v{}", env!("CARGO_PKG_VERSION")).as_str())
+        );
+        assert!(lines.next().unwrap().starts_with("os: "));
+        assert!(report.contains("\npanic: oops\n"));
+        assert!(report.contains("last requests:\n  (none)\n"));
+    }
+
+    #[test]
+    fn args_are_masked() {
+        let args = [
+            "twitchlink".to_string(),
+            "--usher-url".to_string(),
+            "http://localhost/?sig=abc123&type=any".to_string(),
+        ];
+        let report = build("oops", &args, &VecDeque::new(), &"");
+        assert!(report.contains("?sig=<redacted>&type=any"), "{}", report);
+        assert!(!report.contains("abc123"), "{}", report);
+    }
+
+    #[test]
+    fn requests_are_listed() {
+        let requests = vec![("http://localhost/a".to_string(), 200)].into();
+        let report = build(
+            "oops",
+            &[],
+            &requests,
+            &"frame: Authorization: OAuth abc123",
+        );
+        assert!(report.contains("  200 http://localhost/a\n"));
+        assert!(report.contains("Authorization: OAuth <redacted>"));
+    }
+
+    // the only test that touches REQUESTS
+    #[test]
+    fn record_strips_queries_and_keeps_the_last_few() {
+        for n in 0..MAX_REQUESTS + 4 {
+            record(&format!("http://localhost/{}?token=abc123", n), 200);
+        }
+
+        let requests = requests();
+        assert_eq!(requests.len(), MAX_REQUESTS);
+        assert_eq!(requests[0], ("http://localhost/4".to_string(), 200));
+        assert!(requests.iter().all(|(url, _)| !url.contains('?')));
+    }
+}
@@ -0,0 +1,453 @@
+// runs twitchlink against the mock server, `cargo test --features mock`
+use std::io::{BufRead as _, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+
+struct Mock {
+    child: Child,
+    base: String,
+}
+
+impl Mock {
+    fn start() -> Self {
+        Self::start_with(&[])
+    }
+
+    fn start_with(args: &[&str]) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_twitchlink-mock"))
+            .args(["--addr", "127.0.0.1:0"])
+            .args(args)
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("start the mock server");
+
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+        stderr.read_line(&mut line).unwrap();
+        let base = line
+            .trim()
+            .strip_prefix("listening on ")
+            .unwrap_or_else(|| panic!("unexpected output from the mock: {}", line))
+            .to_string();
+
+        // the mock logs every request, keep the pipe from filling up
+        std::thread::spawn(move || stderr.lines().map_while(Result::ok).for_each(drop));
+
+        Self { child, base }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_twitchlink"));
+        cmd.args(args)
+            .env("TWITCH_CLIENT_ID", "mock-client-id")
+            .env("TWITCHLINK_API_URL", &self.base)
+            .env("TWITCHLINK_USHER_URL", &self.base);
+        cmd
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// runs `debug watch-playlist` until `f` is true for a line of its output
+fn watch(mock: &Mock, args: &[&str], stderr: bool, f: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut cmd = mock.command(&[&["debug", "watch-playlist", "--no-color"], args].concat());
+    let mut child = if stderr {
+        cmd.stdout(Stdio::null()).stderr(Stdio::piped()).spawn()
+    } else {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()
+    }
+    .unwrap();
+
+    let output: Box<dyn std::io::Read> = if stderr {
+        Box::new(child.stderr.take().unwrap())
+    } else {
+        Box::new(child.stdout.take().unwrap())
+    };
+
+    let mut lines = vec![];
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        let done = f(&line);
+        lines.push(line);
+        if done {
+            break;
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    lines
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).to_string()
+}
+
+#[test]
+fn list() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let list = stdout(&out);
+    for quality in &["[best]", "[720p]", "[480p]", "[160p]"] {
+        assert!(
+            list.contains(quality),
+            "{} is missing from:\n{}",
+            quality,
+            list
+        );
+    }
+}
+
+#[test]
+fn json() {
+    let mock = Mock::start();
+    let out = mock.run(&["--json", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let streams: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let streams = streams.as_array().expect("an array of streams");
+    assert_eq!(streams.len(), 4);
+    assert!(streams.iter().all(|s| s["link"]
+        .as_str()
+        .is_some_and(|link| link.starts_with(&mock.base))));
+}
+
+#[test]
+fn json_audio_tracks() {
+    let mock = Mock::start();
+    let out = mock.run(&["--json", "-q", "best", "--audio-track", "de", "multiaudio"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let stream: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(stream["audio_group"], "aac");
+    assert_eq!(stream["audio"].as_array().unwrap().len(), 3);
+    assert_eq!(stream["track"]["name"], "Deutsch");
+}
+
+#[test]
+fn several_streams() {
+    let mock = Mock::start();
+    let out = mock.run(&["--json", "foo", "bar"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let entries: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let channels = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["channel"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(channels, ["foo", "bar"]);
+}
+
+#[test]
+fn list_without_bandwidth() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "nobandwidth"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stdout(&out).contains("[720p]"), "{}", stdout(&out));
+}
+
+#[test]
+fn offline() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "offline"]);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("is offline"), "{}", stderr(&out));
+}
+
+#[test]
+fn forbidden() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "forbidden"]);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("was denied"), "{}", stderr(&out));
+}
+
+#[test]
+fn no_token() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "notoken"]);
+    assert!(!out.status.success());
+    assert!(
+        stderr(&out).contains("cannot find token"),
+        "{}",
+        stderr(&out)
+    );
+}
+
+#[test]
+fn keep_going() {
+    let mock = Mock::start();
+    let out = mock.run(&["--list", "--keep-going", "foo", "offline"]);
+    assert!(!out.status.success());
+    assert!(stdout(&out).contains("foo:"), "{}", stdout(&out));
+    assert!(stderr(&out).contains("1 of 2"), "{}", stderr(&out));
+}
+
+#[test]
+fn client_id_is_redacted() {
+    let mock = Mock::start();
+    let out = mock.run(&["--verbose", "--list", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(!stderr(&out).contains("mock-client-id"), "{}", stderr(&out));
+}
+
+#[test]
+fn debug_is_a_channel() {
+    let mock = Mock::start();
+    let out = mock.run(&["debug", "--list"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stdout(&out).contains("[best]"), "{}", stdout(&out));
+}
+
+#[test]
+fn connections_are_reused() {
+    let mock = Mock::start();
+    let out = mock.run(&["--verbose", "--list", "foo"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(
+        stderr(&out).contains("reusing a connection"),
+        "{}",
+        stderr(&out)
+    );
+}
+
+#[test]
+fn shared_client() {
+    let mock = Mock::start();
+    let client = twitchlink::Client::new("mock-client-id")
+        .with_api_url(&mock.base)
+        .with_usher_url(&mock.base);
+
+    std::thread::scope(|scope| {
+        let client = &client;
+        let handles = ["foo", "bar", "foo", "multiaudio"]
+            .iter()
+            .map(|&channel| scope.spawn(move || client.get(channel)))
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let playlist = handle.join().unwrap().unwrap();
+            assert_eq!(playlist.streams.len(), 4);
+        }
+    });
+
+    let err = client.get("forbidden").unwrap_err();
+    assert!(matches!(err, twitchlink::Error::Forbidden(..)), "{}", err);
+}
+
+#[test]
+fn udp_without_a_listener() {
+    let mock = Mock::start();
+
+    // find a port that nothing is listening on
+    let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+        .and_then(|socket| socket.local_addr())
+        .unwrap();
+    let target = format!("udp://{}", addr);
+
+    let mut child = mock
+        .command(&["-q", "best", "-o", &target, "foo"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    let status = child.try_wait().unwrap();
+    let _ = child.kill();
+    let out = child.wait_with_output().unwrap();
+    assert!(status.is_none(), "exited early: {}", stderr(&out));
+}
+
+#[test]
+fn udp() {
+    let mock = Mock::start();
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+        .unwrap();
+    let target = format!("udp://{}", socket.local_addr().unwrap());
+
+    let mut child = mock
+        .command(&["-q", "best", "-o", &target, "foo"])
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut buf = [0; 65536];
+    let datagrams = (0..5)
+        .map(|_| socket.recv(&mut buf).map(|len| buf[..len].to_vec()))
+        .collect::<Result<Vec<_>, _>>();
+    let _ = child.kill();
+    let _ = child.wait();
+
+    for datagram in datagrams.unwrap() {
+        assert_eq!(datagram.len() % 188, 0, "{}", datagram.len());
+        assert!(datagram.chunks(188).all(|packet| packet[0] == 0x47));
+    }
+}
+
+// records the channel to a file until it ends, and returns the file and stderr
+fn record(mock: &Mock, channel: &str) -> (Vec<u8>, String) {
+    let path = std::env::temp_dir().join(format!(
+        "twitchlink-test-{}-{}.ts",
+        std::process::id(),
+        channel
+    ));
+    let out = mock.run(&["-q", "best", "-o", path.to_str().unwrap(), channel]);
+    let data = std::fs::read(&path);
+    let _ = std::fs::remove_file(&path);
+    assert!(out.status.success(), "{}", stderr(&out));
+    (data.unwrap(), stderr(&out))
+}
+
+const SEGMENT_LEN: usize = 64 * 188;
+
+#[test]
+fn record_to_a_file() {
+    let mock = Mock::start();
+    let (data, _) = record(&mock, "ended");
+    assert_eq!(data.len(), 3 * SEGMENT_LEN);
+    assert!(data.chunks(188).all(|packet| packet[0] == 0x47));
+}
+
+#[test]
+fn record_keeps_going() {
+    let mock = Mock::start();
+    let (data, stderr) = record(&mock, "flaky");
+    assert!(stderr.contains("(retrying, 1/10)"), "{}", stderr);
+    assert!(stderr.contains("skipping segment 1"), "{}", stderr);
+    assert_eq!(data.len(), 2 * SEGMENT_LEN);
+}
+
+#[test]
+fn record_after_a_restart() {
+    let mock = Mock::start();
+    let (data, stderr) = record(&mock, "restart");
+    assert!(stderr.contains("went back from sequence 105"), "{}", stderr);
+    // the live edge before the restart, then everything after it
+    assert_eq!(data.len(), 6 * SEGMENT_LEN);
+}
+
+#[test]
+fn watch_playlist_restart() {
+    let mock = Mock::start();
+    let lines = watch(&mock, &["--interval", "1", "restart"], false, |line| {
+        line.contains("new segment(s), sequence 0..=2")
+    });
+    let reset = lines
+        .iter()
+        .position(|line| line == "  ! sequence reset: 105 -> 0");
+    assert_eq!(reset, Some(lines.len() - 2), "{:#?}", lines);
+}
+
+#[test]
+fn ipc_is_only_for_playing() {
+    let mock = Mock::start();
+    for args in &[&["-o", "-"][..], &["--json"], &["--list"]] {
+        let out = mock.run(&[&["--ipc"], *args, &["foo"]].concat());
+        assert!(!out.status.success());
+        assert!(stdout(&out).is_empty());
+        assert!(
+            stderr(&out).contains("can't be used with `--output`"),
+            "{}",
+            stderr(&out)
+        );
+    }
+}
+
+#[test]
+fn followed_channels() {
+    let mock = Mock::start();
+    let client = twitchlink::Client::new("mock-client-id").with_api_url(&mock.base);
+
+    let live = client.followed("oauth:mock-oauth-token").unwrap();
+    assert_eq!(live, ["ads", "multiaudio", "unstable"]);
+
+    let err = client.followed("").unwrap_err();
+    assert!(matches!(err, twitchlink::Error::Status(_, 401)), "{}", err);
+}
+
+#[test]
+fn watch_playlist_unredacted() {
+    let mock = Mock::start();
+    let lines = watch(
+        &mock,
+        &["--verbose", "--log-unredacted", "foo"],
+        true,
+        |line| line.contains("sig="),
+    );
+    let last = lines.last().unwrap();
+    assert!(
+        last.contains("sig=0123456789abcdef0123456789abcdef01234567"),
+        "{}",
+        last
+    );
+}
+
+#[test]
+fn watch_playlist() {
+    let mock = Mock::start();
+    let lines = watch(&mock, &["foo"], false, |line| line.contains("new segment"));
+    assert!(lines
+        .iter()
+        .any(|line| line.contains("+ #EXT-X-STREAM-INF:")));
+    assert!(lines
+        .iter()
+        .any(|line| line == "  ! watching the `best` media playlist"));
+}
+
+#[test]
+fn watch_playlist_ads() {
+    // segment 10 starts an ad break
+    let mock = Mock::start_with(&["--epoch", "20"]);
+    let lines = watch(&mock, &["ads"], false, |line| {
+        line.contains("#15 discontinuity")
+    });
+
+    let events = [
+        "#10 discontinuity: #EXT-X-DISCONTINUITY",
+        "#10 ad marker: #EXT-X-DATERANGE:ID=\"stitched-ad-0\"",
+        "#10 segment title: #EXTINF:2.000,Amazon",
+        "#14 segment title: #EXTINF:2.000,Amazon",
+    ];
+    for event in &events {
+        assert!(
+            lines.iter().any(|line| line.contains(event)),
+            "missing `{}` in {:#?}",
+            event,
+            lines
+        );
+    }
+    assert!(!lines.iter().any(|line| line.contains("#15 segment title")));
+}
+
+#[test]
+fn watch_playlist_unstable() {
+    // a second before the lowest variant goes away and the node changes
+    let mock = Mock::start_with(&["--epoch", "59"]);
+    let lines = watch(&mock, &["--interval", "1", "unstable"], false, |line| {
+        line.starts_with("  - #EXT-X-STREAM-INF:") && line.contains("160p30")
+    });
+
+    let changed = |prefix: &str, node: &str| {
+        lines
+            .iter()
+            .any(|line| line.starts_with(prefix) && line.contains(node))
+    };
+    assert!(changed("  - #EXT-X-TWITCH-INFO:", "mock0"), "{:#?}", lines);
+    assert!(changed("  + #EXT-X-TWITCH-INFO:", "mock1"), "{:#?}", lines);
+    assert!(!lines.iter().any(|line| line.contains("SERVER-TIME")));
+}